@@ -0,0 +1,654 @@
+use anyhow::{bail, Context, Result};
+use peroxide::recover::find_candidates;
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::terminal;
+use peroxide::{build_ssh_command, probe_connection, split_extra_args, text, App, AppError, InputMode, SshConnection, StoredConnections};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+/// Runs a non-interactive subcommand, or returns `None` when the arguments
+/// should start the TUI instead.
+pub fn run(args: &[String]) -> Option<Result<ExitCode>> {
+    match args.first().map(String::as_str) {
+        Some("connect") => Some(connect(&args[1..])),
+        Some("test") => Some(test(&args[1..])),
+        Some("recover") => Some(recover(&args[1..])),
+        _ => None,
+    }
+}
+
+/// How the TUI should start, from the flags given without a subcommand.
+#[derive(Debug, Default, PartialEq)]
+pub struct StartupOptions {
+    pub privacy: bool,
+    pub screen: StartScreen,
+    /// Scenario file for `--simulate`.
+    pub simulate: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub enum StartScreen {
+    #[default]
+    Connections,
+    /// The Add form, optionally pre-filled.
+    Add { host: Option<String>, user: Option<String> },
+    Settings,
+}
+
+impl StartScreen {
+    /// Opens this screen on a freshly loaded `app`.
+    pub fn apply(self, app: &mut App) {
+        match self {
+            StartScreen::Connections => {}
+            StartScreen::Add { host, user } => {
+                app.start_adding();
+                app.form_state.host = host.unwrap_or_default();
+                if let Some(user) = user {
+                    app.form_state.username = user;
+                }
+            }
+            StartScreen::Settings => app.input_mode = InputMode::Settings,
+        }
+    }
+}
+
+/// Parses `[--privacy] [--simulate FILE] [--add [--host H] [--user U] | --settings]`.
+pub fn parse_startup(args: &[String]) -> Result<StartupOptions> {
+    const USAGE: &str = "usage: peroxide [--privacy] [--simulate SCENARIO] [--add [--host HOST] [--user USER] | --settings]";
+    let mut options = StartupOptions::default();
+    let (mut add, mut settings) = (false, false);
+    let (mut host, mut user) = (None, None);
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--privacy" => options.privacy = true,
+            "--add" => add = true,
+            "--settings" => settings = true,
+            "--simulate" => {
+                options.simulate = Some(PathBuf::from(args.next().context("--simulate expects a scenario file")?));
+            }
+            "--host" => host = Some(args.next().context("--host expects a host name")?.clone()),
+            "--user" => user = Some(args.next().context("--user expects a user name")?.clone()),
+            _ => bail!(USAGE),
+        }
+    }
+
+    options.screen = match (add, settings) {
+        (true, true) => bail!("--add and --settings cannot be combined"),
+        (true, false) => StartScreen::Add { host, user },
+        (false, _) if host.is_some() || user.is_some() => bail!("--host and --user only apply with --add"),
+        (false, true) => StartScreen::Settings,
+        (false, false) => StartScreen::Connections,
+    };
+    Ok(options)
+}
+
+/// `peroxide connect <name> [--shell]`: runs ssh for a saved connection
+/// directly, without the TUI, for environments where it cannot take over the
+/// terminal. `--shell` skips the connection's remote command.
+fn connect(args: &[String]) -> Result<ExitCode> {
+    const USAGE: &str = "usage: peroxide connect <name> [--shell]";
+    let shell = args.iter().any(|arg| arg == "--shell");
+    let mut names = args.iter().filter(|arg| *arg != "--shell");
+    let (Some(name), None) = (names.next(), names.next()) else {
+        bail!(USAGE);
+    };
+    if name.starts_with("--") {
+        bail!(USAGE);
+    }
+    let mut app = load_app()?;
+    let idx = find_connection(&app, name)?;
+    unlock_secrets_for(&mut app, &[idx])?;
+    let mut conn = app.connection_for_use(idx).context("Connection disappeared")?;
+    if shell {
+        conn.remote_command = None;
+    }
+    if let Some(args) = &conn.extra_args {
+        split_extra_args(args).map_err(anyhow::Error::msg)?;
+    }
+
+    let clock_warning = app.clock_skew_warning(&conn);
+    let expiry_warning = app.expiry_warning(&conn);
+    for warning in app.ssh_client.unsupported_options(&conn).into_iter().chain(clock_warning).chain(expiry_warning) {
+        eprintln!("peroxide: {}", warning);
+    }
+    app.connections[idx].last_connected = Some(chrono::Utc::now());
+    let status = build_ssh_command(&conn, &app.link_options(&conn), &app.ssh_client)
+        .status()
+        .context("Failed to execute SSH")?;
+    app.connections[idx].use_count += 1;
+    write_connections(&mut app)?;
+    Ok(ExitCode::from(status.code().unwrap_or(1).clamp(0, 255) as u8))
+}
+
+/// `peroxide test <name>|--all [--timeout N] [--quiet]`: runs the same check
+/// as the TUI's connection test, by default with the configured timeout.
+/// Exit codes are stable so scripts can branch on the cause:
+///
+/// - 0: success
+/// - 1: usage error, or with `--all`, any connection failed
+/// - 2: authentication failed
+/// - 3: host unreachable
+/// - 4: host name could not be resolved
+/// - 5: timed out
+/// - 6: the SOCKS5 proxy failed
+fn test(args: &[String]) -> Result<ExitCode> {
+    const USAGE: &str = "usage: peroxide test <name>|--all [--timeout N] [--quiet]";
+    let mut name = None;
+    let mut all = false;
+    let mut quiet = false;
+    let mut app = load_app()?;
+    let mut timeout = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--all" => all = true,
+            "--quiet" => quiet = true,
+            "--timeout" => {
+                let secs: u64 = args
+                    .next()
+                    .and_then(|secs| secs.parse().ok())
+                    .filter(|&secs| secs > 0)
+                    .context("--timeout expects a positive number of seconds")?;
+                timeout = Some(Duration::from_secs(secs));
+            }
+            _ if name.is_none() && !arg.starts_with("--") => name = Some(arg.clone()),
+            _ => bail!(USAGE),
+        }
+    }
+
+    if all {
+        if name.is_some() {
+            bail!(USAGE);
+        }
+        let indices: Vec<usize> = (0..app.connections.len())
+            .filter(|&idx| !app.connections[idx].archived && !app.is_expired(idx))
+            .filter(|&idx| !app.connections[idx].ask_password || app.connections[idx].has_password())
+            .collect();
+        unlock_secrets_for(&mut app, &indices)?;
+        let connections: Vec<(SshConnection, Duration)> = indices
+            .into_iter()
+            .filter_map(|idx| app.connection_for_use(idx))
+            .map(|conn| {
+                let timeout = timeout.unwrap_or_else(|| app.link_options(&conn).connect_timeout);
+                (conn, timeout)
+            })
+            .collect();
+        return Ok(test_all(&connections, quiet));
+    }
+
+    let Some(name) = name else {
+        bail!(USAGE);
+    };
+    let idx = find_connection(&app, &name)?;
+    unlock_secrets_for(&mut app, &[idx])?;
+    let conn = app.connection_for_use(idx).context("Connection disappeared")?;
+
+    let timeout = timeout.unwrap_or_else(|| app.link_options(&conn).connect_timeout);
+    let result = probe_connection(&conn, Some(timeout));
+    if !quiet {
+        match &result {
+            Ok(()) => println!("{}: ok", conn.name),
+            Err(e) => println!("{}: {}", conn.name, e),
+        }
+    }
+    Ok(ExitCode::from(exit_code(&result)))
+}
+
+fn test_all(connections: &[(SshConnection, Duration)], quiet: bool) -> ExitCode {
+    let results: Vec<Result<(), AppError>> = thread::scope(|scope| {
+        let handles: Vec<_> = connections
+            .iter()
+            .map(|(conn, timeout)| scope.spawn(move || probe_connection(conn, Some(*timeout))))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(AppError::ConnectionFailed("test panicked".to_string())))
+            })
+            .collect()
+    });
+
+    if !quiet {
+        let width = connections
+            .iter()
+            .map(|(conn, _)| text::display_width(&conn.name))
+            .max()
+            .unwrap_or(0)
+            .max("NAME".len());
+        println!("{}  {:<6}  DETAIL", text::pad("NAME", width), "RESULT");
+        for ((conn, _), result) in connections.iter().zip(&results) {
+            match result {
+                Ok(()) => println!("{}  {:<6}", text::pad(&conn.name, width), "ok"),
+                Err(e) => println!("{}  {:<6}  {}", text::pad(&conn.name, width), "FAILED", e),
+            }
+        }
+        let failed = results.iter().filter(|result| result.is_err()).count();
+        println!("\n{} passed, {} failed", results.len() - failed, failed);
+    }
+
+    if results.iter().all(Result::is_ok) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn exit_code(result: &Result<(), AppError>) -> u8 {
+    match result {
+        Ok(()) => 0,
+        Err(
+            AppError::AuthenticationFailed(_)
+            | AppError::KeyPermissions(_)
+            | AppError::PassphraseRequired
+            | AppError::PasswordRequired,
+        ) => 2,
+        Err(AppError::ConnectionFailed(_)) => 3,
+        Err(AppError::ResolutionFailed(_)) => 4,
+        Err(AppError::TimedOut(_)) => 5,
+        Err(AppError::ProxyFailed(_)) => 6,
+        Err(AppError::UnknownHostKey(_) | AppError::HostKeyChanged(..)) => 7,
+        Err(AppError::NoConnectionSelected) => 1,
+    }
+}
+
+/// `peroxide recover [--yes] [--dry-run]`: rebuilds connections from
+/// known_hosts and shell history. Candidates are listed for the user to
+/// pick from, and the chosen ones are written under `WritePolicy`: with
+/// `--yes`, after a y/N answer on a terminal, and never when stdin is not a
+/// terminal without `--yes`; `--dry-run` only lists them. Passwords and
+/// passphrases cannot be recovered, so only key paths seen on `ssh -i`
+/// come back.
+fn recover(args: &[String]) -> Result<ExitCode> {
+    let (policy, rest) = WritePolicy::parse(args);
+    if !rest.is_empty() {
+        bail!("usage: peroxide recover [--yes] [--dry-run]");
+    }
+    let home = dirs::home_dir().context("Could not find home directory")?;
+    let mut app = App::new();
+    // A damaged file is what recover is for, but an encrypted one must be
+    // unlocked or left alone. One that cannot be read is kept under another
+    // name before anything is written over it.
+    let unreadable = match load_connections(&mut app) {
+        Err(e) if app.locked.is_some() => return Err(e),
+        Err(e) => {
+            eprintln!("peroxide: could not read connections.json: {:#}; it will be kept under another name", e);
+            true
+        }
+        Ok(()) => false,
+    };
+    let candidates = find_candidates(&home, &app.connections);
+    if candidates.is_empty() {
+        println!("No connections found to recover.");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let width = candidates.iter().map(|c| text::display_width(&c.name())).max().unwrap_or(0);
+    for (i, candidate) in candidates.iter().enumerate() {
+        println!("{:>3}. {}  {}", i + 1, text::pad(&candidate.name(), width), candidate.sources.join(", "));
+    }
+    print!("\nCreate which connections? (e.g. 1,3-5 or 'all'; empty to cancel): ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    let chosen = parse_selection(answer.trim(), candidates.len())
+        .context("Expected numbers and ranges such as 1,3-5, or 'all'")?;
+    if chosen.is_empty() {
+        println!("Nothing created.");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let default_user = std::env::var("USER").unwrap_or_else(|_| "root".to_string());
+    let created: Vec<SshConnection> = candidates
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| chosen.contains(i))
+        .map(|(_, candidate)| candidate.into_connection(&default_user))
+        .collect();
+    let changes: Vec<Change> = created.iter().map(|conn| Change::create(&conn.name)).collect();
+    if !policy.confirm(&changes)? {
+        return Ok(ExitCode::SUCCESS);
+    }
+    if unreadable {
+        if let Some(moved) = App::set_aside_connections_file()? {
+            println!("Moved the unreadable connections.json to {}", moved.display());
+        }
+    }
+    let count = created.len();
+    app.connections.extend(created);
+    write_connections(&mut app)?;
+    println!("Created {} connections without passwords; add credentials in peroxide.", count);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Saves the connections as every subcommand does: connections.json is
+/// copied to `connections.json.bak` first, then replaced atomically like
+/// the TUI saves it.
+fn write_connections(app: &mut App) -> Result<()> {
+    App::back_up_connections_file()?;
+    app.save_connections()
+}
+
+/// How a subcommand that writes the connection store gets permission to:
+/// `--yes`, or a y/N answer when stdin is a terminal. Without either it
+/// refuses, so scripts cannot change anything by accident. `--dry-run`
+/// lists the changes and writes nothing.
+#[derive(Debug, Default, Clone, Copy)]
+struct WritePolicy {
+    yes: bool,
+    dry_run: bool,
+}
+
+impl WritePolicy {
+    /// Takes `--yes`/`-y` and `--dry-run` out of `args`, returning the rest.
+    fn parse(args: &[String]) -> (Self, Vec<String>) {
+        let mut policy = Self::default();
+        let mut rest = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "--yes" | "-y" => policy.yes = true,
+                "--dry-run" => policy.dry_run = true,
+                _ => rest.push(arg.clone()),
+            }
+        }
+        (policy, rest)
+    }
+
+    /// Prints `changes` and decides whether to write them.
+    fn confirm(self, changes: &[Change]) -> Result<bool> {
+        let interactive = io::stdin().is_terminal();
+        self.decide(changes, interactive, &mut io::stdin().lock(), &mut io::stdout())
+    }
+
+    /// [`confirm`](Self::confirm) with the terminal check and both streams
+    /// supplied by the caller.
+    fn decide(self, changes: &[Change], interactive: bool, input: &mut impl BufRead, out: &mut impl Write) -> Result<bool> {
+        if changes.is_empty() {
+            writeln!(out, "No changes.")?;
+            return Ok(false);
+        }
+        for change in changes {
+            writeln!(out, "  {:<7} {}", change.action, change.name)?;
+        }
+        if self.dry_run {
+            writeln!(out, "Dry run: nothing written.")?;
+            return Ok(false);
+        }
+        if self.yes {
+            return Ok(true);
+        }
+        if !interactive {
+            bail!("Refusing to write without --yes when stdin is not a terminal");
+        }
+        write!(out, "Write these changes? [y/N] ")?;
+        out.flush()?;
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        let confirmed = matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes");
+        if !confirmed {
+            writeln!(out, "Nothing written.")?;
+        }
+        Ok(confirmed)
+    }
+}
+
+/// One entry a subcommand is about to write, by connection name.
+struct Change {
+    action: &'static str,
+    name: String,
+}
+
+impl Change {
+    fn create(name: &str) -> Self {
+        Self { action: "create", name: name.to_string() }
+    }
+}
+
+/// Parses `1,3-5` or `all` into zero-based indices below `count`.
+fn parse_selection(input: &str, count: usize) -> Option<Vec<usize>> {
+    if input.eq_ignore_ascii_case("all") {
+        return Some((0..count).collect());
+    }
+    let mut chosen = Vec::new();
+    for part in input.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+            None => {
+                let n: usize = part.parse().ok()?;
+                (n, n)
+            }
+        };
+        if start == 0 || end < start || end > count {
+            return None;
+        }
+        chosen.extend(start - 1..end);
+    }
+    chosen.sort_unstable();
+    chosen.dedup();
+    Some(chosen)
+}
+
+/// Loads connections and settings the way the TUI does at startup. A
+/// "prompt once per session" passphrase source has no session here, so
+/// only keyring passphrases apply.
+fn load_app() -> Result<App> {
+    let mut app = App::new();
+    load_connections(&mut app)?;
+    if let Some(warning) = app.secure_config_files() {
+        eprintln!("peroxide: {}", warning);
+    }
+    app.apply_settings(App::load_settings()?);
+    for problem in app.ssh_client.program_problems() {
+        eprintln!("peroxide: {}", problem);
+    }
+    Ok(app)
+}
+
+/// Reads the connections, asking for the master password on the terminal
+/// when they are encrypted.
+fn load_connections(app: &mut App) -> Result<()> {
+    match App::read_connections()? {
+        StoredConnections::Plain(connections) => app.connections = connections,
+        StoredConnections::Encrypted(sealed) => {
+            app.locked = Some(sealed);
+            if !io::stdin().is_terminal() {
+                bail!("connections.json is encrypted; run peroxide from a terminal to enter the master password");
+            }
+            let password = read_secret("Master password: ")?;
+            app.unlock(&password)?;
+        }
+        StoredConnections::SealedSecrets(vault, connections) => {
+            app.vault = Some(vault);
+            app.connections = connections;
+        }
+    }
+    Ok(())
+}
+
+/// Asks for the master password on the terminal when any of the
+/// connections at `indices` has secrets sealed under it.
+fn unlock_secrets_for(app: &mut App, indices: &[usize]) -> Result<()> {
+    if !app.secrets_locked() || !indices.iter().any(|&idx| app.connections[idx].has_sealed_secrets()) {
+        return Ok(());
+    }
+    if !io::stdin().is_terminal() {
+        bail!("the connection's secrets are encrypted; run peroxide from a terminal to enter the master password");
+    }
+    let password = read_secret("Master password: ")?;
+    app.unlock_secrets(&password)
+}
+
+/// Reads a line from the terminal without echoing it.
+fn read_secret(prompt: &str) -> Result<String> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    terminal::enable_raw_mode()?;
+    let result = (|| -> Result<Option<String>> {
+        let mut secret = String::new();
+        loop {
+            if let Event::Key(key) = event::read()? {
+                match key.code {
+                    KeyCode::Enter => return Ok(Some(secret)),
+                    KeyCode::Esc => return Ok(None),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+                    KeyCode::Backspace => {
+                        secret.pop();
+                    }
+                    KeyCode::Char(c) => secret.push(c),
+                    _ => {}
+                }
+            }
+        }
+    })();
+    terminal::disable_raw_mode()?;
+    println!();
+    result?.context("Cancelled")
+}
+
+fn find_connection(app: &App, name: &str) -> Result<usize> {
+    app.connections
+        .iter()
+        .position(|conn| conn.name == name)
+        .with_context(|| format!("No connection named '{}'", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn startup(line: &str) -> Result<StartupOptions> {
+        parse_startup(&args(line))
+    }
+
+    fn decide(policy: WritePolicy, interactive: bool, answer: &str) -> (Result<bool>, String) {
+        let changes = [Change::create("web"), Change::create("db-primary")];
+        let mut out = Vec::new();
+        let result = policy.decide(&changes, interactive, &mut answer.as_bytes(), &mut out);
+        (result, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn write_policy_flags_are_taken_out_of_the_args() {
+        let (policy, rest) = WritePolicy::parse(&args("-y extra --dry-run"));
+        assert!(policy.yes && policy.dry_run);
+        assert_eq!(rest, args("extra"));
+        let (policy, rest) = WritePolicy::parse(&args("--yes"));
+        assert!(policy.yes && !policy.dry_run);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn dry_run_lists_changes_and_writes_nothing() {
+        let expected = "  create  web\n  create  db-primary\nDry run: nothing written.\n";
+        for yes in [false, true] {
+            let (result, output) = decide(WritePolicy { yes, dry_run: true }, false, "");
+            assert!(!result.unwrap());
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn refuses_to_write_without_yes_when_not_interactive() {
+        let (result, output) = decide(WritePolicy::default(), false, "y\n");
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("without --yes"), "{}", err);
+        assert!(!output.contains("[y/N]"));
+
+        let (result, output) = decide(WritePolicy { yes: true, dry_run: false }, false, "");
+        assert!(result.unwrap());
+        assert!(!output.contains("[y/N]"));
+    }
+
+    #[test]
+    fn interactive_write_needs_a_yes_answer() {
+        assert!(decide(WritePolicy::default(), true, "Yes\n").0.unwrap());
+        let (result, output) = decide(WritePolicy::default(), true, "\n");
+        assert!(!result.unwrap());
+        assert!(output.ends_with("[y/N] Nothing written.\n"), "{}", output);
+    }
+
+    #[test]
+    fn no_changes_write_nothing() {
+        let mut out = Vec::new();
+        let policy = WritePolicy { yes: true, dry_run: false };
+        assert!(!policy.decide(&[], false, &mut io::empty(), &mut out).unwrap());
+        assert_eq!(out, b"No changes.\n");
+    }
+
+    #[test]
+    fn selections_parse_ranges_and_all() {
+        assert_eq!(parse_selection("1,3-5", 5), Some(vec![0, 2, 3, 4]));
+        assert_eq!(parse_selection(" 2 , 2-3 ", 3), Some(vec![1, 2]));
+        assert_eq!(parse_selection("ALL", 2), Some(vec![0, 1]));
+        assert_eq!(parse_selection("", 4), Some(Vec::new()));
+        assert_eq!(parse_selection("0", 4), None);
+        assert_eq!(parse_selection("3-2", 4), None);
+        assert_eq!(parse_selection("5", 4), None);
+        assert_eq!(parse_selection("x", 4), None);
+    }
+
+    #[test]
+    fn no_flags_start_on_the_connection_list() {
+        assert_eq!(startup("").unwrap(), StartupOptions::default());
+        let options = startup("--privacy --simulate demo.json").unwrap();
+        assert!(options.privacy);
+        assert_eq!(options.simulate, Some(PathBuf::from("demo.json")));
+        assert_eq!(options.screen, StartScreen::Connections);
+    }
+
+    #[test]
+    fn add_takes_optional_host_and_user() {
+        assert_eq!(startup("--add").unwrap().screen, StartScreen::Add { host: None, user: None });
+        assert_eq!(
+            startup("--user root --add --host db.internal").unwrap().screen,
+            StartScreen::Add { host: Some("db.internal".to_string()), user: Some("root".to_string()) }
+        );
+        assert_eq!(startup("--settings").unwrap().screen, StartScreen::Settings);
+    }
+
+    #[test]
+    fn conflicting_or_incomplete_flags_are_errors() {
+        let error = |line: &str| startup(line).unwrap_err().to_string();
+        assert_eq!(error("--add --settings"), "--add and --settings cannot be combined");
+        assert_eq!(error("--host web"), "--host and --user only apply with --add");
+        assert_eq!(error("--settings --user root"), "--host and --user only apply with --add");
+        assert_eq!(error("--add --host"), "--host expects a host name");
+        assert_eq!(error("--simulate"), "--simulate expects a scenario file");
+        assert!(error("--bogus").starts_with("usage: peroxide"));
+    }
+
+    #[test]
+    fn start_screens_set_the_initial_mode_and_form() {
+        let mut app = App::new();
+        app.persist = false;
+        StartScreen::Add { host: Some("db.internal".to_string()), user: Some("root".to_string()) }.apply(&mut app);
+        assert_eq!(app.input_mode, InputMode::Adding);
+        assert_eq!(app.form_state.host, "db.internal");
+        assert_eq!(app.form_state.username, "root");
+
+        let mut app = App::new();
+        app.persist = false;
+        app.default_username = "deploy".to_string();
+        StartScreen::Add { host: None, user: None }.apply(&mut app);
+        assert_eq!(app.form_state.username, "deploy");
+        assert!(app.form_state.host.is_empty());
+
+        let mut app = App::new();
+        StartScreen::Settings.apply(&mut app);
+        assert_eq!(app.input_mode, InputMode::Settings);
+        let mut app = App::new();
+        StartScreen::Connections.apply(&mut app);
+        assert_eq!(app.input_mode, InputMode::Normal);
+    }
+}
@@ -0,0 +1,116 @@
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch.
+const NTP_UNIX_OFFSET: f64 = 2_208_988_800.0;
+const SNTP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The optional check that warns before certificate logins when the local
+/// clock is off by more than `threshold_secs` from an SNTP server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClockSkewSettings {
+    pub enabled: bool,
+    /// `host[:port]` of an SNTP server; the port defaults to 123.
+    pub server: String,
+    pub threshold_secs: u64,
+}
+
+impl Default for ClockSkewSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            server: "pool.ntp.org".to_string(),
+            threshold_secs: 60,
+        }
+    }
+}
+
+/// How far the local clock is ahead of `server`, in seconds (negative when
+/// behind), from a single SNTP query. `None` when the server cannot be
+/// reached or answers with nonsense.
+pub fn measure_skew(server: &str) -> Option<f64> {
+    let addr = if server.contains(':') { server.to_string() } else { format!("{}:123", server) };
+    let addr = addr.to_socket_addrs().ok()?.next()?;
+    let socket = UdpSocket::bind(if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }).ok()?;
+    socket.set_read_timeout(Some(SNTP_TIMEOUT)).ok()?;
+
+    // LI 0, version 3, mode 3 (client).
+    let mut packet = [0u8; 48];
+    packet[0] = 0x1b;
+    let sent = unix_now()?;
+    socket.send_to(&packet, addr).ok()?;
+    let (len, _) = socket.recv_from(&mut packet).ok()?;
+    let received = unix_now()?;
+    if len < 48 {
+        return None;
+    }
+
+    let secs = u32::from_be_bytes(packet[40..44].try_into().ok()?) as f64;
+    let fraction = u32::from_be_bytes(packet[44..48].try_into().ok()?) as f64 / 4_294_967_296.0;
+    if secs == 0.0 {
+        return None;
+    }
+    let server_time = secs + fraction - NTP_UNIX_OFFSET;
+    Some((sent + received) / 2.0 - server_time)
+}
+
+fn unix_now() -> Option<f64> {
+    SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|now| now.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// Answers one SNTP query on loopback with `reply`, built from the
+    /// current time, and returns the server's address.
+    fn fake_server(reply: impl FnOnce(f64) -> Vec<u8> + Send + 'static) -> String {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let mut query = [0u8; 48];
+            let (_, client) = socket.recv_from(&mut query).unwrap();
+            assert_eq!(query[0], 0x1b);
+            socket.send_to(&reply(unix_now().unwrap()), client).unwrap();
+        });
+        addr
+    }
+
+    /// An SNTP answer whose transmit timestamp is `unix` seconds.
+    fn answer(unix: f64) -> Vec<u8> {
+        let ntp = unix + NTP_UNIX_OFFSET;
+        let mut packet = vec![0u8; 48];
+        packet[0] = 0x1c;
+        packet[40..44].copy_from_slice(&(ntp.trunc() as u32).to_be_bytes());
+        packet[44..48].copy_from_slice(&((ntp.fract() * 4_294_967_296.0) as u32).to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn measures_how_far_ahead_the_server_is() {
+        let server = fake_server(|now| answer(now + 100.0));
+        let skew = measure_skew(&server).unwrap();
+        assert!((skew + 100.0).abs() < 1.0, "{}", skew);
+
+        let server = fake_server(|now| answer(now - 42.5));
+        let skew = measure_skew(&server).unwrap();
+        assert!((skew - 42.5).abs() < 1.0, "{}", skew);
+    }
+
+    #[test]
+    fn nonsense_answers_are_ignored() {
+        assert_eq!(measure_skew(&fake_server(|_| vec![0x1c; 20])), None);
+        assert_eq!(measure_skew(&fake_server(|_| vec![0u8; 48])), None);
+    }
+
+    #[test]
+    fn defaults_to_a_public_pool_and_one_minute() {
+        let settings: ClockSkewSettings = serde_json::from_str(r#"{"enabled": true}"#).unwrap();
+        assert!(settings.enabled);
+        assert_eq!(settings.server, "pool.ntp.org");
+        assert_eq!(settings.threshold_secs, 60);
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::file_browser::FileBrowser;
+use crate::transfer::Direction;
+
+/// The side of the dual browser that keys go to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Local,
+    Remote,
+}
+
+/// A file copied from one side to the other. A move removes the source
+/// once the copy has arrived.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedCopy {
+    pub direction: Direction,
+    pub local: PathBuf,
+    pub remote: String,
+    pub is_move: bool,
+}
+
+impl QueuedCopy {
+    /// Where the file ends up.
+    pub fn target(&self) -> String {
+        match self.direction {
+            Direction::Upload => self.remote.clone(),
+            Direction::Download => self.local.display().to_string(),
+        }
+    }
+
+    /// One line of the queue strip, as `↑ copy notes.txt → /srv/notes.txt`.
+    pub fn describe(&self) -> String {
+        let (arrow, source) = match self.direction {
+            Direction::Upload => ("↑", file_name(&self.local)),
+            Direction::Download => ("↓", file_name(Path::new(&self.remote))),
+        };
+        let verb = if self.is_move { "move" } else { "copy" };
+        format!("{} {} {} → {}", arrow, verb, source, self.target())
+    }
+}
+
+/// Local files on the left, a connection's files over SFTP on the right,
+/// and the copies queued between them. The copies run one at a time
+/// through `App::transfer`.
+#[derive(Debug)]
+pub struct DualBrowser {
+    /// The connection the remote pane browses.
+    pub idx: usize,
+    pub local: FileBrowser,
+    pub remote: FileBrowser,
+    pub focus: Pane,
+    /// Copies waiting for the running one to finish.
+    pub queue: VecDeque<QueuedCopy>,
+    /// The copy `App::transfer` is running for this browser.
+    pub running: Option<QueuedCopy>,
+}
+
+impl DualBrowser {
+    pub fn new(idx: usize, local: FileBrowser, remote: FileBrowser) -> Self {
+        Self { idx, local, remote, focus: Pane::Local, queue: VecDeque::new(), running: None }
+    }
+
+    pub fn toggle_focus(&mut self) {
+        self.focus = match self.focus {
+            Pane::Local => Pane::Remote,
+            Pane::Remote => Pane::Local,
+        };
+    }
+
+    /// Whether the remote session is gone; its pane then takes no keys
+    /// until it is reconnected.
+    pub fn is_disconnected(&self) -> bool {
+        self.remote.disconnected
+    }
+
+    /// The focused pane, unless it is the remote one and disconnected.
+    pub fn focused_mut(&mut self) -> Option<&mut FileBrowser> {
+        match self.focus {
+            Pane::Local => Some(&mut self.local),
+            Pane::Remote if self.remote.disconnected => None,
+            Pane::Remote => Some(&mut self.remote),
+        }
+    }
+
+    /// Queues the file selected in the focused pane for the directory
+    /// shown in the other one. A name that is already there, or already
+    /// on its way there, is refused rather than overwritten.
+    pub fn queue_selected(&mut self, is_move: bool) -> Result<&QueuedCopy, String> {
+        if self.remote.disconnected {
+            return Err("The remote side is disconnected; r reconnects".to_string());
+        }
+        let (from, to) = match self.focus {
+            Pane::Local => (&self.local, &self.remote),
+            Pane::Remote => (&self.remote, &self.local),
+        };
+        let source = from
+            .get_selected_path()
+            .filter(|path| !from.is_dir(path))
+            .ok_or_else(|| "Select a file to copy; folders are not copied".to_string())?;
+        let name = source.file_name().unwrap_or_default();
+        let target = to.current_path.join(name);
+        if to.entries.contains(&target) {
+            return Err(format!("{} is already in {}", name.to_string_lossy(), to.current_path.display()));
+        }
+        let copy = match self.focus {
+            Pane::Local => QueuedCopy {
+                direction: Direction::Upload,
+                local: source,
+                remote: target.to_string_lossy().into_owned(),
+                is_move,
+            },
+            Pane::Remote => QueuedCopy {
+                direction: Direction::Download,
+                local: target,
+                remote: source.to_string_lossy().into_owned(),
+                is_move,
+            },
+        };
+        if self.running.iter().chain(&self.queue).any(|queued| queued.target() == copy.target()) {
+            return Err(format!("{} is already queued", copy.target()));
+        }
+        self.queue.push_back(copy);
+        Ok(self.queue.back().expect("just queued"))
+    }
+
+    /// Takes the next queued copy to run, unless one is running.
+    pub fn start_next(&mut self) -> Option<QueuedCopy> {
+        if self.running.is_some() {
+            return None;
+        }
+        self.running = self.queue.pop_front();
+        self.running.clone()
+    }
+
+    /// Notes that the running copy ended with `result`: a finished move
+    /// removes its source. Both panes are listed again to show the
+    /// change. Returns why the source could not be removed, if it could
+    /// not.
+    pub fn finish(&mut self, result: &Result<u64, String>) -> Option<String> {
+        let copy = self.running.take()?;
+        let removed = match (result, copy.is_move, copy.direction) {
+            (Ok(_), true, Direction::Upload) => Some(fs::remove_file(&copy.local).map_err(|e| e.to_string())),
+            (Ok(_), true, Direction::Download) => {
+                Some(self.remote.remove_file(Path::new(&copy.remote)).map_err(|e| format!("{:#}", e)))
+            }
+            _ => None,
+        };
+        self.local.refresh_keeping_selection();
+        if !self.remote.disconnected {
+            self.remote.refresh_keeping_selection();
+        }
+        removed?.err()
+    }
+
+    /// Drops the copies that have not started; returns how many there were.
+    pub fn clear_queue(&mut self) -> usize {
+        let dropped = self.queue.len();
+        self.queue.clear();
+        dropped
+    }
+
+    /// Puts a freshly connected remote pane in place of the old one, back
+    /// in the directory the old one showed when it can still be listed.
+    pub fn reconnect(&mut self, mut remote: FileBrowser) {
+        remote.show_hidden = self.remote.show_hidden;
+        if !remote.open_directory(self.remote.current_path.clone()) {
+            remote.error = None;
+        }
+        self.remote = remote;
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh, empty directory under the system temp dir.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("peroxide-dual-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A dual browser over two local folders, the right one standing in
+    /// for the remote host.
+    fn dual(name: &str) -> (DualBrowser, PathBuf, PathBuf) {
+        let dir = scratch_dir(name);
+        let (left, right) = (dir.join("left"), dir.join("right"));
+        fs::create_dir_all(left.join("folder")).unwrap();
+        fs::create_dir_all(&right).unwrap();
+        fs::write(left.join("notes.txt"), b"notes").unwrap();
+        fs::write(right.join("report.pdf"), b"report").unwrap();
+        let browser = DualBrowser::new(0, FileBrowser::new(left.clone()), FileBrowser::new(right.clone()));
+        (browser, left, right)
+    }
+
+    fn select(browser: &mut FileBrowser, path: &Path) {
+        browser.selected = browser.entries.iter().position(|entry| entry == path).unwrap();
+    }
+
+    #[test]
+    fn files_queue_for_the_other_side_of_the_focus() {
+        let (mut dual, left, right) = dual("queue");
+        select(&mut dual.local, &left.join("notes.txt"));
+        let upload = dual.queue_selected(false).unwrap().clone();
+        assert_eq!(upload.direction, Direction::Upload);
+        assert_eq!(upload.local, left.join("notes.txt"));
+        assert_eq!(upload.remote, right.join("notes.txt").to_string_lossy());
+
+        dual.toggle_focus();
+        assert_eq!(dual.focus, Pane::Remote);
+        select(&mut dual.remote, &right.join("report.pdf"));
+        let download = dual.queue_selected(true).unwrap().clone();
+        assert_eq!(download.direction, Direction::Download);
+        assert_eq!(download.local, left.join("report.pdf"));
+        assert!(download.describe().starts_with("↓ move report.pdf → "), "{}", download.describe());
+
+        assert_eq!(dual.start_next(), Some(upload));
+        assert_eq!(dual.start_next(), None, "one copy runs at a time");
+        assert_eq!(dual.queue.len(), 1);
+        fs::remove_dir_all(left.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn folders_existing_names_and_repeats_are_refused() {
+        let (mut dual, left, right) = dual("refused");
+        select(&mut dual.local, &left.join("folder"));
+        assert!(dual.queue_selected(false).is_err());
+
+        fs::write(right.join("notes.txt"), b"older notes").unwrap();
+        dual.remote.refresh_keeping_selection();
+        select(&mut dual.local, &left.join("notes.txt"));
+        let err = dual.queue_selected(false).unwrap_err();
+        assert!(err.starts_with("notes.txt is already in"), "{}", err);
+
+        fs::remove_file(right.join("notes.txt")).unwrap();
+        dual.remote.refresh_keeping_selection();
+        dual.queue_selected(false).unwrap();
+        assert!(dual.queue_selected(true).unwrap_err().ends_with("is already queued"));
+        assert_eq!(dual.clear_queue(), 1);
+        fs::remove_dir_all(left.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn a_finished_move_removes_its_source_and_a_failed_one_keeps_it() {
+        let (mut dual, left, right) = dual("move");
+        select(&mut dual.local, &left.join("notes.txt"));
+        dual.queue_selected(true).unwrap();
+        dual.start_next().unwrap();
+        assert_eq!(dual.finish(&Err("link dropped".to_string())), None);
+        assert!(left.join("notes.txt").exists());
+
+        dual.queue_selected(true).unwrap();
+        dual.start_next().unwrap();
+        // The transfer engine would have written the copy by now.
+        fs::copy(left.join("notes.txt"), right.join("notes.txt")).unwrap();
+        assert_eq!(dual.finish(&Ok(5)), None);
+        assert!(!left.join("notes.txt").exists());
+        assert!(!dual.local.entries.contains(&left.join("notes.txt")), "the panes are listed again");
+        assert!(dual.remote.entries.contains(&right.join("notes.txt")));
+        fs::remove_dir_all(left.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn a_disconnected_remote_takes_no_keys_or_copies() {
+        let (mut dual, left, _right) = dual("disconnected");
+        dual.remote.disconnected = true;
+        dual.toggle_focus();
+        assert!(dual.focused_mut().is_none());
+        dual.toggle_focus();
+        assert!(dual.focused_mut().is_some());
+        select(&mut dual.local, &left.join("notes.txt"));
+        assert!(dual.queue_selected(false).unwrap_err().contains("r reconnects"));
+        fs::remove_dir_all(left.parent().unwrap()).unwrap();
+    }
+}
@@ -1,11 +1,43 @@
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::fs;
+use std::fmt;
+use anyhow::{Context, Result};
+use ssh2::{ErrorCode, Session, Sftp};
 
 #[derive(Debug)]
 pub struct FileBrowser {
     pub current_path: PathBuf,
     pub entries: Vec<PathBuf>,
     pub selected: usize,
+    remote: Option<RemoteFs>,
+    /// Directories among `entries` when browsing a remote host, which
+    /// cannot be asked with `Path::is_dir`.
+    remote_dirs: HashSet<PathBuf>,
+    /// Whether entries whose name starts with `.` are listed.
+    pub show_hidden: bool,
+    /// Lists only directories and files that `is_valid_ssh_key` accepts.
+    pub keys_only: bool,
+    /// Why the last directory could not be listed, such as permission
+    /// denied; shown in the browser until a listing succeeds.
+    pub error: Option<String>,
+    /// Set when the remote session itself failed rather than one
+    /// directory; nothing more can be listed until a new session is opened.
+    pub disconnected: bool,
+}
+
+/// An SFTP session to browse instead of the local filesystem. The session
+/// is kept so it lives as long as the SFTP channel.
+struct RemoteFs {
+    _session: Session,
+    sftp: Sftp,
+    host: String,
+}
+
+impl fmt::Debug for RemoteFs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteFs").field("host", &self.host).finish_non_exhaustive()
+    }
 }
 
 impl FileBrowser {
@@ -14,11 +46,49 @@ impl FileBrowser {
             current_path: start_path,
             entries: Vec::new(),
             selected: 0,
+            remote: None,
+            remote_dirs: HashSet::new(),
+            show_hidden: false,
+            keys_only: false,
+            error: None,
+            disconnected: false,
         };
         browser.refresh_entries();
         browser
     }
 
+    /// Browses `host` over an authenticated session, starting in the remote
+    /// user's home directory.
+    pub fn new_remote(session: Session, host: String) -> Result<Self> {
+        let sftp = session.sftp().context("Failed to start SFTP")?;
+        let start_path = sftp.realpath(Path::new(".")).context("Failed to find the remote home directory")?;
+        let mut browser = Self {
+            current_path: start_path,
+            entries: Vec::new(),
+            selected: 0,
+            remote: Some(RemoteFs { _session: session, sftp, host }),
+            remote_dirs: HashSet::new(),
+            show_hidden: false,
+            keys_only: false,
+            error: None,
+            disconnected: false,
+        };
+        browser.refresh_entries();
+        Ok(browser)
+    }
+
+    /// The remote host being browsed, if any.
+    pub fn remote_host(&self) -> Option<&str> {
+        self.remote.as_ref().map(|remote| remote.host.as_str())
+    }
+
+    pub fn is_dir(&self, path: &Path) -> bool {
+        match &self.remote {
+            Some(_) => path == self.current_path || path.ends_with("..") || self.remote_dirs.contains(path),
+            None => path.is_dir(),
+        }
+    }
+
     pub fn refresh_entries(&mut self) {
         let mut entries = Vec::new();
         
@@ -28,13 +98,49 @@ impl FileBrowser {
             entries.push(self.current_path.join(".."));
         }
 
-        if let Ok(read_dir) = fs::read_dir(&self.current_path) {
-            for entry in read_dir.flatten() {
-                let path = entry.path();
-                if path.is_dir() || path.is_file() {
-                    entries.push(path);
+        self.error = None;
+        if let Some(remote) = &self.remote {
+            self.remote_dirs.clear();
+            match remote.sftp.readdir(&self.current_path) {
+                Ok(read_dir) => {
+                    for (path, stat) in read_dir {
+                        if stat.is_dir() {
+                            self.remote_dirs.insert(path.clone());
+                            entries.push(path);
+                        } else if stat.is_file() {
+                            entries.push(path);
+                        }
+                    }
+                }
+                Err(e) => {
+                    self.disconnected = matches!(e.code(), ErrorCode::Session(_));
+                    self.error = Some(e.message().to_string());
                 }
             }
+        } else {
+            match fs::read_dir(&self.current_path) {
+                Ok(read_dir) => {
+                    for entry in read_dir.flatten() {
+                        let path = entry.path();
+                        if path.is_dir() || path.is_file() {
+                            entries.push(path);
+                        }
+                    }
+                }
+                Err(e) => self.error = Some(e.to_string()),
+            }
+        }
+
+        if !self.show_hidden {
+            let current_path = &self.current_path;
+            entries.retain(|path| {
+                path == current_path
+                    || path.ends_with("..")
+                    || !path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+            });
+        }
+        if self.keys_only {
+            entries.retain(|path| self.is_dir(path) || self.is_valid_ssh_key(path));
         }
 
         entries.sort_by(|a, b| {
@@ -45,9 +151,9 @@ impl FileBrowser {
                 std::cmp::Ordering::Less
             } else if !a_is_special && b_is_special {
                 std::cmp::Ordering::Greater
-            } else if a.is_dir() && !b.is_dir() {
+            } else if self.is_dir(a) && !self.is_dir(b) {
                 std::cmp::Ordering::Less
-            } else if !a.is_dir() && b.is_dir() {
+            } else if !self.is_dir(a) && self.is_dir(b) {
                 std::cmp::Ordering::Greater
             } else {
                 a.file_name()
@@ -60,23 +166,74 @@ impl FileBrowser {
         self.selected = 0;
     }
 
+    /// Enters the selected directory, or the parent for `..`. A directory
+    /// that cannot be listed is not entered, and `error` says why.
     pub fn enter_directory(&mut self) -> bool {
-        if self.selected < self.entries.len() {
-            let selected_path = &self.entries[self.selected];
-            
-            if selected_path.ends_with("..") {
-                if let Some(parent) = self.current_path.parent() {
-                    self.current_path = parent.to_path_buf();
-                    self.refresh_entries();
-                    return true;
-                }
-            } else if selected_path.is_dir() {
-                self.current_path = selected_path.clone();
-                self.refresh_entries();
-                return true;
+        let Some(selected_path) = self.entries.get(self.selected) else {
+            return false;
+        };
+        let target = if selected_path.ends_with("..") {
+            match self.current_path.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => return false,
             }
+        } else if self.is_dir(selected_path) {
+            selected_path.clone()
+        } else {
+            return false;
+        };
+        self.open_directory(target)
+    }
+
+    /// Lists `target` instead of the current directory; like
+    /// `enter_directory`, one that cannot be listed is not kept.
+    pub fn open_directory(&mut self, target: PathBuf) -> bool {
+        let (selected, name) = (self.selected, target.display().to_string());
+        let previous = std::mem::replace(&mut self.current_path, target);
+        self.refresh_entries();
+        if let Some(error) = self.error.take() {
+            self.current_path = previous;
+            self.refresh_entries();
+            self.selected = selected.min(self.entries.len().saturating_sub(1));
+            self.error = Some(format!("Cannot open {}: {}", name, error));
+            return false;
+        }
+        true
+    }
+
+    /// Shows or hides dotfiles, keeping the selection on the same entry
+    /// when it is still listed.
+    pub fn toggle_hidden(&mut self) {
+        self.show_hidden = !self.show_hidden;
+        self.refresh_keeping_selection();
+    }
+
+    /// Lists only directories and likely key files, or everything again.
+    pub fn toggle_keys_only(&mut self) {
+        self.keys_only = !self.keys_only;
+        self.refresh_keeping_selection();
+    }
+
+    /// Lists the current directory again, keeping the selection on the
+    /// same entry when it is still there.
+    pub fn refresh_keeping_selection(&mut self) {
+        let selected = self.get_selected_path();
+        self.refresh_entries();
+        if let Some(idx) = selected.and_then(|path| self.entries.iter().position(|entry| *entry == path)) {
+            self.selected = idx;
+        }
+    }
+
+    /// Deletes the file at `path`, locally or over SFTP.
+    pub fn remove_file(&mut self, path: &Path) -> Result<()> {
+        let Some(remote) = &self.remote else {
+            return fs::remove_file(path).with_context(|| path.display().to_string());
+        };
+        if let Err(e) = remote.sftp.unlink(path) {
+            self.disconnected |= matches!(e.code(), ErrorCode::Session(_));
+            anyhow::bail!("{}: {}", path.display(), e.message());
         }
-        false
+        Ok(())
     }
 
     pub fn move_up(&mut self) {
@@ -111,7 +268,7 @@ impl FileBrowser {
     }
 
     pub fn get_display_name(&self, path: &Path) -> String {
-        if path == &self.current_path {
+        if path == self.current_path {
             ".".to_string()
         } else if path.ends_with("..") {
             "..".to_string()
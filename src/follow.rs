@@ -0,0 +1,233 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use ssh2::{ExtendedData, Session};
+use crate::AppError;
+
+/// Lines of the file shown before following starts.
+const BACKLOG_LINES: usize = 200;
+/// Bytes without a newline after which the stream is cut into a line
+/// anyway, so a runaway line cannot grow without bound.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+/// How long the reader sleeps when no data is waiting.
+const IDLE_SLEEP: Duration = Duration::from_millis(50);
+/// Keepalives notice a dropped connection while the file is quiet.
+const KEEPALIVE_SECS: u32 = 15;
+const LIBSSH2_ERROR_EAGAIN: i32 = -37;
+
+/// What the reader thread reports.
+#[derive(Debug)]
+enum FollowEvent {
+    Lines(Vec<String>),
+    /// The stream is over, with the reason.
+    Ended(String),
+}
+
+/// `tail -F` of a remote file, read on a background thread. Lines are
+/// picked up with `poll` from the event loop; dropping the follow stops
+/// the thread and closes the channel.
+#[derive(Debug)]
+pub struct Follow {
+    /// Index and name of the connection, for reconnecting.
+    pub idx: usize,
+    pub connection: String,
+    pub path: String,
+    receiver: Receiver<FollowEvent>,
+    stop: Arc<AtomicBool>,
+    /// While paused, new lines are held back so the view keeps still.
+    pub paused: bool,
+    held: Vec<String>,
+    /// Why the stream ended, once it has.
+    pub ended: Option<String>,
+    /// The local file every received line is appended to, while on.
+    output: Option<(PathBuf, File)>,
+}
+
+impl Follow {
+    /// Starts following `path`. `connect` opens and authenticates the
+    /// session; it runs on the reader thread so the UI does not wait for
+    /// it.
+    pub fn start<F>(idx: usize, connection: String, path: String, connect: F) -> Self
+    where
+        F: FnOnce() -> Result<Session, AppError> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let command = tail_command(&path);
+        let thread_stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            let reason = match connect() {
+                Ok(sess) => stream(&sess, &command, &sender, &thread_stop),
+                Err(e) => e.to_string(),
+            };
+            let _ = sender.send(FollowEvent::Ended(reason));
+        });
+        Self {
+            idx,
+            connection,
+            path,
+            receiver,
+            stop,
+            paused: false,
+            held: Vec::new(),
+            ended: None,
+            output: None,
+        }
+    }
+
+    /// Lines received since the last call, unless paused. Lines are
+    /// written to the output file as they arrive, paused or not.
+    pub fn poll(&mut self) -> Vec<String> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(FollowEvent::Lines(lines)) => {
+                    if let Some((path, file)) = &mut self.output {
+                        let written = lines.iter().try_for_each(|line| writeln!(file, "{}", line));
+                        if let Err(e) = written {
+                            self.ended.get_or_insert(format!("Could not write {}: {}", path.display(), e));
+                            self.output = None;
+                        }
+                    }
+                    self.held.extend(lines);
+                }
+                Ok(FollowEvent::Ended(reason)) => {
+                    self.ended.get_or_insert(reason);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.ended.get_or_insert_with(|| "The stream stopped".to_string());
+                    break;
+                }
+            }
+        }
+        if self.paused {
+            Vec::new()
+        } else {
+            std::mem::take(&mut self.held)
+        }
+    }
+
+    /// Lines waiting to be shown while paused.
+    pub fn held(&self) -> usize {
+        self.held.len()
+    }
+
+    /// The file lines are being written to, if any.
+    pub fn output_path(&self) -> Option<&PathBuf> {
+        self.output.as_ref().map(|(path, _)| path)
+    }
+
+    /// Starts writing lines to a new file in `dir`, or stops writing.
+    /// Returns the file started.
+    pub fn toggle_output(&mut self, dir: PathBuf) -> Result<Option<PathBuf>> {
+        if self.output.take().is_some() {
+            return Ok(None);
+        }
+        let base = self.path.rsplit('/').find(|part| !part.is_empty()).unwrap_or("log");
+        let name = format!(
+            "{}-{}-{}.log",
+            self.connection,
+            base,
+            chrono::Local::now().format("%Y-%m-%dT%H-%M-%S")
+        )
+        .replace(['/', '\\'], "_");
+        fs::create_dir_all(&dir).with_context(|| format!("Could not create {}", dir.display()))?;
+        let path = dir.join(name);
+        let file = File::create(&path).with_context(|| format!("Could not create {}", path.display()))?;
+        self.output = Some((path.clone(), file));
+        Ok(Some(path))
+    }
+}
+
+impl Drop for Follow {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// `tail -F` of `path`, quoted for the remote shell. A leading `~/` is
+/// left for the shell to expand.
+fn tail_command(path: &str) -> String {
+    let quote = |part: &str| shlex::try_quote(part).map(|quoted| quoted.into_owned()).unwrap_or_default();
+    let target = match path.strip_prefix("~/") {
+        Some(rest) => format!("\"$HOME\"/{}", quote(rest)),
+        None => quote(path),
+    };
+    format!("exec tail -n {} -F -- {}", BACKLOG_LINES, target)
+}
+
+/// Runs `command` and sends its output line by line until it exits, the
+/// connection drops, the receiver goes away or `stop` is set. Returns why
+/// it ended.
+fn stream(sess: &Session, command: &str, sender: &Sender<FollowEvent>, stop: &AtomicBool) -> String {
+    let mut channel = match sess.channel_session() {
+        Ok(channel) => channel,
+        Err(e) => return format!("Could not open a channel: {}", e),
+    };
+    // tail reports a missing or rotated file on stderr; show it in line.
+    let _ = channel.handle_extended_data(ExtendedData::Merge);
+    if let Err(e) = channel.exec(command) {
+        return format!("Could not run tail: {}", e);
+    }
+    sess.set_keepalive(true, KEEPALIVE_SECS);
+    sess.set_blocking(false);
+
+    let mut pending: Vec<u8> = Vec::new();
+    let mut buf = [0u8; 8192];
+    let mut next_keepalive = Instant::now();
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            sess.set_blocking(true);
+            sess.set_timeout(1000);
+            let _ = channel.close();
+            return String::new();
+        }
+        match channel.read(&mut buf) {
+            Ok(0) if channel.eof() => {
+                let mut lines = Vec::new();
+                if !pending.is_empty() {
+                    lines.push(String::from_utf8_lossy(&pending).into_owned());
+                }
+                let _ = sender.send(FollowEvent::Lines(lines));
+                sess.set_blocking(true);
+                sess.set_timeout(1000);
+                let _ = channel.wait_close();
+                return match channel.exit_status() {
+                    Ok(status) => format!("tail exited with status {}", status),
+                    Err(_) => "tail exited".to_string(),
+                };
+            }
+            Ok(0) => thread::sleep(IDLE_SLEEP),
+            Ok(n) => {
+                pending.extend_from_slice(&buf[..n]);
+                let mut lines = Vec::new();
+                while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = pending.drain(..=end).collect();
+                    lines.push(String::from_utf8_lossy(&line[..end]).trim_end_matches('\r').to_string());
+                }
+                if pending.len() > MAX_LINE_BYTES {
+                    lines.push(String::from_utf8_lossy(&pending).into_owned());
+                    pending.clear();
+                }
+                if !lines.is_empty() && sender.send(FollowEvent::Lines(lines)).is_err() {
+                    return String::new();
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(IDLE_SLEEP),
+            Err(e) => return format!("Connection lost: {}", e),
+        }
+        if Instant::now() >= next_keepalive {
+            match sess.keepalive_send() {
+                Ok(secs) => next_keepalive = Instant::now() + Duration::from_secs(secs.max(1) as u64),
+                Err(e) if e.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_EAGAIN) => {}
+                Err(e) => return format!("Connection lost: {}", e),
+            }
+        }
+    }
+}
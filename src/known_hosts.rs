@@ -0,0 +1,288 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+use base64ct::{Base64, Base64Unpadded, Encoding};
+use sha2::{Digest, Sha256};
+use ssh2::{CheckResult, HashType, KnownHostFileKind, MethodType, Session};
+use crate::AppError;
+
+/// The key a server presented during the handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostKey {
+    pub host: String,
+    pub port: u16,
+    /// Key type as written in known_hosts, e.g. `ssh-ed25519`.
+    pub key_type: String,
+    /// `SHA256:` and unpadded base64, as `ssh` prints it.
+    pub fingerprint: String,
+    /// The key in base64, as written in known_hosts.
+    key: String,
+}
+
+/// What the fingerprint view shows about a server's key.
+#[derive(Debug)]
+pub struct HostKeyInfo {
+    pub key: HostKey,
+    /// `MD5:` and colon-separated hex, as `ssh -o FingerprintHash=md5`
+    /// prints it.
+    pub md5: String,
+    /// How the key compares with known_hosts: `Ok`, or the
+    /// `UnknownHostKey` or `HostKeyChanged` error connecting would give.
+    pub status: Result<(), AppError>,
+}
+
+/// A known_hosts line naming the host being checked.
+#[derive(Debug, Clone)]
+pub struct KnownKey {
+    key_type: String,
+    key: String,
+}
+
+impl KnownKey {
+    /// `SHA256:` fingerprint of the key, as `ssh-keygen -l` prints it.
+    fn fingerprint(&self) -> String {
+        let blob = base64_decode(&self.key).unwrap_or_default();
+        format!("SHA256:{}", Base64Unpadded::encode_string(&Sha256::digest(blob)))
+    }
+}
+
+impl HostKey {
+    /// How known_hosts names this host: the bare name on port 22, otherwise
+    /// `[host]:port`.
+    pub fn pattern(&self) -> String {
+        pattern(&self.host, self.port)
+    }
+
+    /// Appends the key to `~/.ssh/known_hosts`, creating the file if needed.
+    pub fn trust(&self) -> Result<PathBuf> {
+        let path = known_hosts_path().context("No home directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+        }
+        let existing = fs::read(&path).unwrap_or_default();
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Could not open {}", path.display()))?;
+        let separator = if existing.last().is_some_and(|&b| b != b'\n') { "\n" } else { "" };
+        writeln!(file, "{}{} {} {}", separator, self.pattern(), self.key_type, self.key)
+            .with_context(|| format!("Could not write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+pub fn known_hosts_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".ssh").join("known_hosts"))
+}
+
+fn pattern(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+/// The entries of `~/.ssh/known_hosts` for `host:port`. libssh2 does the
+/// name matching, so hashed entries count too; its key comparison ignores
+/// the key type, so that part is done here. Lines with a marker
+/// (`@cert-authority`, `@revoked`) are not supported and skipped.
+pub fn known_keys(sess: &Session, host: &str, port: u16) -> Vec<KnownKey> {
+    let Some(content) = known_hosts_path().and_then(|path| fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    keys_in(sess, &content, host, port)
+}
+
+/// The lines of known_hosts `content` naming `host:port`.
+fn keys_in(sess: &Session, content: &str, host: &str, port: u16) -> Vec<KnownKey> {
+    let Ok(mut entries) = sess.known_hosts() else {
+        return Vec::new();
+    };
+    let pattern = pattern(host, port);
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('@'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let (key_type, key) = (fields.next()?, fields.next()?);
+            // One line at a time, so a match belongs to this line.
+            for stale in entries.iter().ok()? {
+                entries.remove(&stale).ok()?;
+            }
+            entries.read_str(line, KnownHostFileKind::OpenSSH).ok()?;
+            // Any key differs from a single zero byte, so a mismatch means
+            // the name matched.
+            matches!(entries.check(&pattern, &[0]), CheckResult::Mismatch).then(|| KnownKey {
+                key_type: key_type.to_string(),
+                key: key.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Puts the algorithms of the keys already known for the host first, as
+/// `ssh` does, so a server with several keys is not reported as unknown
+/// just because libssh2 prefers another type. Call before the handshake.
+pub fn prefer_known_types(sess: &Session, known: &[KnownKey]) {
+    let Ok(supported) = sess.supported_algs(MethodType::HostKey) else {
+        return;
+    };
+    let mut order: Vec<&str> = supported
+        .iter()
+        .copied()
+        .filter(|alg| known.iter().any(|entry| entry.key_type == key_type_of(alg)))
+        .collect();
+    if order.is_empty() {
+        return;
+    }
+    order.extend(supported.iter().copied().filter(|alg| !order.contains(alg)).collect::<Vec<_>>());
+    let _ = sess.method_pref(MethodType::HostKey, &order.join(","));
+}
+
+/// The key type an algorithm signs with; RSA keys have several.
+fn key_type_of(alg: &str) -> &str {
+    if alg.starts_with("rsa-sha2-") {
+        "ssh-rsa"
+    } else {
+        alg
+    }
+}
+
+/// Compares the key the server presented with `known` (from `known_keys`).
+/// An unknown host is `AppError::UnknownHostKey`, for the caller to offer
+/// trusting it; a different key of a known type is
+/// `AppError::HostKeyChanged`, with the fingerprints known_hosts expected,
+/// and must not be connected to.
+pub fn verify(sess: &Session, host: &str, port: u16, known: &[KnownKey]) -> Result<(), AppError> {
+    compare(presented_key(sess, host, port)?, known)
+}
+
+/// The server's key and how it compares with `known`, for showing without
+/// connecting. Call after the handshake.
+pub fn inspect(sess: &Session, host: &str, port: u16, known: &[KnownKey]) -> Result<HostKeyInfo, AppError> {
+    let key = presented_key(sess, host, port)?;
+    let md5 = sess
+        .host_key_hash(HashType::Md5)
+        .map(|hash| hash.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":"))
+        .unwrap_or_default();
+    let status = compare(key.clone(), known);
+    Ok(HostKeyInfo { key, md5: format!("MD5:{}", md5), status })
+}
+
+/// The key the server presented during the handshake.
+fn presented_key(sess: &Session, host: &str, port: u16) -> Result<HostKey, AppError> {
+    let (blob, _) = sess
+        .host_key()
+        .ok_or_else(|| AppError::ConnectionFailed("The server sent no host key".to_string()))?;
+    let key_type = blob
+        .get(..4)
+        .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+        .and_then(|len| blob.get(4..4 + len))
+        .and_then(|name| std::str::from_utf8(name).ok())
+        .ok_or_else(|| AppError::ConnectionFailed("The server sent a malformed host key".to_string()))?;
+    let fingerprint = sess.host_key_hash(HashType::Sha256).unwrap_or_default();
+    Ok(HostKey {
+        host: host.to_string(),
+        port,
+        key_type: key_type.to_string(),
+        fingerprint: format!("SHA256:{}", Base64Unpadded::encode_string(fingerprint)),
+        key: Base64::encode_string(blob),
+    })
+}
+
+fn compare(presented: HostKey, known: &[KnownKey]) -> Result<(), AppError> {
+    let same_type: Vec<&KnownKey> = known.iter().filter(|entry| entry.key_type == presented.key_type).collect();
+    if same_type.iter().any(|entry| entry.key == presented.key) {
+        Ok(())
+    } else if same_type.is_empty() {
+        Err(AppError::UnknownHostKey(presented))
+    } else {
+        let expected: Vec<String> = same_type.iter().map(|entry| entry.fingerprint()).collect();
+        Err(AppError::HostKeyChanged(presented, expected.join(", ").into()))
+    }
+}
+
+/// Decodes padded base64, as known_hosts and key files write it.
+pub(crate) fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    Base64::decode_vec(text).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprint_matches_ssh_keygen() {
+        let key = KnownKey {
+            key_type: "ssh-ed25519".to_string(),
+            key: "AAAAC3NzaC1lZDI1NTE5AAAAIGUec4wKKfylyMe2vTkIU4FHMwj8B3FP7JFx0OxYgpsB".to_string(),
+        };
+        assert_eq!(key.fingerprint(), "SHA256:hy1vmJcdOUkqVuxYSGdPNPvdkE12Q5BbZEsQ3udyFvg");
+    }
+
+    const ED25519: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIGUec4wKKfylyMe2vTkIU4FHMwj8B3FP7JFx0OxYgpsB";
+
+    fn presented(key_type: &str, key: &str) -> HostKey {
+        HostKey {
+            host: "web.example.com".to_string(),
+            port: 22,
+            key_type: key_type.to_string(),
+            fingerprint: String::new(),
+            key: key.to_string(),
+        }
+    }
+
+    fn known(key_type: &str, key: &str) -> KnownKey {
+        KnownKey { key_type: key_type.to_string(), key: key.to_string() }
+    }
+
+    #[test]
+    fn a_listed_key_is_accepted() {
+        let entries = [known("ssh-rsa", "AAAA"), known("ssh-ed25519", ED25519)];
+        assert!(compare(presented("ssh-ed25519", ED25519), &entries).is_ok());
+    }
+
+    #[test]
+    fn a_host_without_a_key_of_that_type_is_unknown() {
+        let key = presented("ssh-ed25519", ED25519);
+        assert!(matches!(compare(key.clone(), &[]), Err(AppError::UnknownHostKey(k)) if k == key));
+        assert!(matches!(compare(key, &[known("ssh-rsa", "AAAA")]), Err(AppError::UnknownHostKey(_))));
+    }
+
+    #[test]
+    fn a_different_key_of_the_same_type_is_a_change() {
+        let key = presented("ssh-ed25519", "AAAAC3NzaC1lZDI1NTE5AAAAIA==");
+        match compare(key, &[known("ssh-ed25519", ED25519)]) {
+            Err(AppError::HostKeyChanged(_, expected)) => {
+                assert_eq!(&*expected, "SHA256:hy1vmJcdOUkqVuxYSGdPNPvdkE12Q5BbZEsQ3udyFvg")
+            }
+            other => panic!("expected a changed key, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn every_line_naming_the_host_is_found() {
+        let content = format!(
+            "web.example.com ssh-ed25519 {0}\n\
+             other.example.com ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAAAgQC7\n\
+             # web.example.com ssh-rsa AAAA\n\
+             [web.example.com]:2222 ssh-ed25519 {0}\n\
+             db,web.example.com ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBA==\n",
+            ED25519
+        );
+        let sess = Session::new().unwrap();
+        let types = |port| keys_in(&sess, &content, "web.example.com", port).into_iter().map(|k| k.key_type).collect::<Vec<_>>();
+        assert_eq!(types(22), ["ssh-ed25519", "ecdsa-sha2-nistp256"]);
+        assert_eq!(types(2222), ["ssh-ed25519"]);
+    }
+
+    #[test]
+    fn non_default_ports_are_bracketed() {
+        assert_eq!(pattern("web.example.com", 22), "web.example.com");
+        assert_eq!(pattern("web.example.com", 2222), "[web.example.com]:2222");
+    }
+}
@@ -1,24 +1,115 @@
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{prelude::*, widgets::*};
+use std::collections::HashSet;
 use std::io;
-use peroxide::{App, AppError, FormState, InputMode, FileBrowserMode, ConfirmationMode};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use peroxide::transfer::Direction as TransferDirection;
+use peroxide::dual_browser::Pane;
+use peroxide::{AfterUnlock, FileBrowser, certificate_path, PublicKey, copy_to_clipboard, is_security_key, sibling_certificate, App, AppError, StoredConnections, InputMode, FileBrowserMode, ConfirmationMode, FieldValue, HostKeyPolicy, KeySource, ListRow, PassphraseSource, PasswordUse, PromptKind, SettingsTab, SortOrder, ViewerKind, FORM_FIELD_COUNT, GENERAL_SETTINGS_COUNT, NOTES_FIELD};
+use peroxide::{mode_description, text};
+use peroxide::text_viewer::{TextViewer, ViewerAction};
+use peroxide::simulate::Scenario;
+use peroxide::theme::Theme;
+use peroxide::timefmt::TimestampStyle;
 
-fn main() -> Result<()> {
+mod cli;
+
+/// How long the event loop waits for input before redrawing, so background
+/// results show up without a keypress.
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+fn main() -> Result<ExitCode> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(result) = cli::run(&args) {
+        return result;
+    }
+
+    let options = cli::parse_startup(&args)?;
+
+    let scenario = match &options.simulate {
+        Some(path) => Some(Scenario::load(path)?),
+        None => None,
+    };
+
+    install_panic_hook();
     let mut terminal = setup_terminal()?;
     let mut app = App::new();
+    app.privacy_mode = options.privacy;
     
-    if let Ok(connections) = App::load_connections() {
-        app.connections = connections;
+    if let Some(scenario) = scenario {
+        start_simulation(&mut app, scenario);
+    } else {
+        match App::read_connections() {
+            Ok(StoredConnections::Plain(connections)) => app.connections = connections,
+            Ok(StoredConnections::Encrypted(sealed)) => app.locked = Some(sealed),
+            Ok(StoredConnections::SealedSecrets(vault, connections)) => {
+                app.vault = Some(vault);
+                app.connections = connections;
+            }
+            Err(e) => {
+                // Saving now would overwrite whatever is there.
+                app.persist = false;
+                app.show_error(format!("Could not load connections: {:#}; nothing will be saved", e));
+            }
+        }
+        if let Some(warning) = app.secure_config_files() {
+            app.show_error(warning);
+        }
+    }
+    if let Ok(settings) = App::load_settings() {
+        app.apply_settings(settings);
+    }
+    for problem in app.ssh_client.program_problems() {
+        app.show_error(problem);
+    }
+    match Theme::load() {
+        Ok(theme) => app.theme = theme,
+        Err(e) => app.show_error(format!("{:#}; using the default theme", e)),
+    }
+    options.screen.apply(&mut app);
+    if app.locked.is_some() {
+        app.input_mode = InputMode::Unlock;
     }
     
-    run(&mut terminal, app)?;
-    restore_terminal(&mut terminal)?;
-    Ok(())
+    let result = run(&mut terminal, app);
+    restore_terminal()?;
+    terminal.show_cursor()?;
+    result?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The connection list key `key` stands for when asking whether it needs
+/// the master password: itself, or `c` for Enter on a connection.
+fn list_key(app: &App, key: KeyEvent) -> Option<char> {
+    match key.code {
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => Some(c),
+        KeyCode::Enter if app.selected_group.is_none() => Some('c'),
+        _ => None,
+    }
+}
+
+/// With vim navigation on, a plain `j` or `k` in the connection list or
+/// Settings becomes `Down` or `Up`.
+fn vim_navigation(app: &App, key: KeyEvent) -> KeyEvent {
+    if !app.vim_keys
+        || !matches!(app.input_mode, InputMode::Normal | InputMode::Settings)
+        || key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    {
+        return key;
+    }
+    match key.code {
+        KeyCode::Char('j') => KeyEvent::new(KeyCode::Down, KeyModifiers::NONE),
+        KeyCode::Char('k') => KeyEvent::new(KeyCode::Up, KeyModifiers::NONE),
+        _ => key,
+    }
 }
 
 fn setup_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
@@ -28,100 +119,197 @@ fn setup_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
     Ok(Terminal::new(CrosstermBackend::new(stdout))?)
 }
 
-fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
+fn restore_terminal() -> Result<()> {
     disable_raw_mode()?;
     execute!(
-        terminal.backend_mut(),
+        io::stdout(),
         LeaveAlternateScreen,
         DisableMouseCapture
     )?;
     Ok(())
 }
 
+/// Puts the terminal back before the panic message is printed, so it is
+/// readable and the shell is not left in raw mode. Panics on worker
+/// threads leave it alone; the interface carries on without them.
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().name() == Some("main") {
+            let _ = restore_terminal();
+            let _ = execute!(io::stdout(), crossterm::cursor::Show);
+        }
+        default_hook(info);
+    }));
+}
+
 fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut app: App) -> Result<()> {
     if let Ok(additional_keys) = App::load_additional_keys() {
         for key in additional_keys {
-            app.add_key_path(key);
+            app.add_key_path(key.path, key.source);
         }
+        // Rewrites files still in the old bare-path format.
+        let _ = app.save_additional_keys();
     }
 
+    // A connection-list key the command palette chose, handled next as
+    // if it had been pressed.
+    let mut replay: Option<KeyEvent> = None;
     loop {
-        terminal.draw(|f| ui(f, &app))?;
+        app.poll_test_run();
+        app.poll_follow();
+        app.poll_transfer();
+        app.lock_if_idle();
+        terminal.draw(|f| ui(f, &mut app))?;
 
-        if let Event::Key(key) = event::read()? {
+        let event = match replay.take() {
+            Some(key) => Event::Key(key),
+            None => {
+                if !event::poll(EVENT_POLL_INTERVAL)? {
+                    continue;
+                }
+                match event::read()? {
+                    Event::Key(key) => Event::Key(vim_navigation(&app, key)),
+                    event => event,
+                }
+            }
+        };
+        if let Event::Resize(..) = event {
+            // Repaint everything at the new size; a partial diff can leave
+            // stale cells behind on some terminals.
+            terminal.autoresize()?;
+            terminal.clear()?;
+            continue;
+        }
+        if let Event::Key(key) = event {
+            app.note_key_press();
             app.clear_error();
+            let mode_before = mode_description(&app);
             
             match app.input_mode {
+                InputMode::Normal if list_key(&app, key).is_some_and(|c| app.needs_master_password(c)) => {
+                    if let Some(c) = list_key(&app, key) {
+                        app.start_prompt(PromptKind::MasterPassword(AfterUnlock::Key(c)));
+                    }
+                }
                 InputMode::Normal => match key.code {
                     KeyCode::Char('q') => {
                         app.save_connections()?;
+                        app.empty_key_trash();
+                        app.save_additional_keys()?;
                         return Ok(());
                     }
-                    KeyCode::Char('a') => {
-                        app.input_mode = InputMode::Adding;
-                        app.form_state = FormState::new();
-                    }
+                    KeyCode::Char('a') => app.start_adding(),
                     KeyCode::Char('e') => {
                         app.edit_connection();
                     }
+                    KeyCode::Char('d') if !app.marked.is_empty() => {
+                        app.confirm_action(ConfirmationMode::DeleteMarked);
+                    }
                     KeyCode::Char('d') => {
                         app.delete_connection();
                     }
+                    KeyCode::Char('b') => browse_selected(&mut app),
+                    KeyCode::Char('B') => browse_both_selected(&mut app),
+                    KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => app.open_palette(),
+                    KeyCode::Char('p') => copy_command_selected(&mut app),
+                    KeyCode::Char('v') => match app.selected_connection {
+                        Some(idx) => {
+                            if let Err(e) = app.show_host_fingerprint(idx) {
+                                app.show_error(format!("Could not read the host key: {}", e));
+                            }
+                        }
+                        None => app.show_error("No connection selected"),
+                    },
+                    KeyCode::Char('i') => {
+                        if app.selected_connection.is_some() {
+                            app.input_mode = InputMode::Details;
+                        } else {
+                            app.show_error("No connection selected");
+                        }
+                    }
+                    KeyCode::Char('U') => {
+                        if let Err(e) = app.select_upload_file() {
+                            app.show_error(e.to_string());
+                        }
+                    }
+                    KeyCode::Char('D') => {
+                        if app.selected_connection.is_some() {
+                            app.start_prompt(PromptKind::DownloadSource);
+                        } else {
+                            app.show_error("No connection selected");
+                        }
+                    }
+                    KeyCode::Char('x') => {
+                        if app.selected_connection.is_some() {
+                            app.start_prompt(PromptKind::RunCommand);
+                        } else {
+                            app.show_error("No connection selected");
+                        }
+                    }
+                    KeyCode::Char('F') => {
+                        if app.selected_connection.is_some() {
+                            app.start_prompt(PromptKind::FollowPath);
+                        } else {
+                            app.show_error("No connection selected");
+                        }
+                    }
+                    KeyCode::Char('h') => match app.toggle_archived() {
+                        Some(archived) => {
+                            app.save_connections()?;
+                            if archived && !app.show_archived {
+                                app.show_error("Archived; H shows archived connections");
+                            }
+                        }
+                        None => app.show_error("No connection selected"),
+                    },
+                    KeyCode::Char('H') => app.toggle_show_archived(),
+                    KeyCode::Char('E') => app.select_backup_folder(),
+                    KeyCode::Char('I') => app.select_import_file(),
+                    KeyCode::Char('X') => app.select_encrypted_export_folder(),
+                    KeyCode::Char('g') => app.start_prompt(PromptKind::QuickConnect),
+                    KeyCode::Char('u') => match app.undo() {
+                        Some(message) => {
+                            app.save_connections()?;
+                            app.show_error(message);
+                        }
+                        None => app.show_error("Nothing to undo"),
+                    },
                     KeyCode::Char('y') => {
                         if let Err(e) = app.duplicate_connection() {
                             app.show_error(e);
                         }
                     }
-                    KeyCode::Up => {
-                        if let Some(selected) = app.selected_connection {
-                            if selected > 0 {
-                                app.selected_connection = Some(selected - 1);
-                            }
-                        } else if !app.connections.is_empty() {
-                            app.selected_connection = Some(0);
+                    KeyCode::Up | KeyCode::Down | KeyCode::Char('K') | KeyCode::Char('J')
+                        if key.modifiers.contains(KeyModifiers::SHIFT) || matches!(key.code, KeyCode::Char(_)) =>
+                    {
+                        let direction = if matches!(key.code, KeyCode::Up | KeyCode::Char('K')) { -1 } else { 1 };
+                        if app.sort_order != SortOrder::Manual {
+                            app.show_error("Switch to manual order (o) to move connections");
+                        } else if app.move_selected_connection(direction) {
+                            app.save_connections()?;
                         }
                     }
-                    KeyCode::Down => {
-                        if let Some(selected) = app.selected_connection {
-                            if selected < app.connections.len().saturating_sub(1) {
-                                app.selected_connection = Some(selected + 1);
-                            }
-                        } else if !app.connections.is_empty() {
-                            app.selected_connection = Some(0);
-                        }
-                    }
-                    KeyCode::Char('c') => {
-                        if let Some(idx) = app.selected_connection {
-                            match app.test_connection(idx) {
-                                Ok(_) => {
-                                    match app.execute_ssh() {
-                                        Ok(needs_redraw) => {
-                                            if needs_redraw {
-                                                terminal.clear()?;
-                                                terminal.draw(|f| ui(f, &app))?;
-                                            }
-                                        }
-                                        Err(e) => {
-                                            app.show_error(format!("Failed to execute SSH: {}", e));
-                                        }
-                                    }
-                                }
-                                Err(e) => match e {
-                                    AppError::ConnectionFailed(msg) => {
-                                        app.show_error(format!("Connection test failed: {}", msg));
-                                    }
-                                    AppError::AuthenticationFailed(msg) => {
-                                        app.show_error(format!("Authentication test failed: {}", msg));
-                                    }
-                                    AppError::NoConnectionSelected => {
-                                        app.show_error("No connection selected");
-                                    }
-                                },
-                            }
-                        } else {
-                            app.show_error("No connection selected");
+                    KeyCode::Up => app.select_previous(),
+                    KeyCode::Down => app.select_next(),
+                    KeyCode::Char('/') => app.start_search(),
+                    KeyCode::Char('o') => {
+                        app.cycle_sort_order();
+                        if let Err(e) = app.save_settings() {
+                            app.show_error(format!("Failed to save settings: {}", e));
+                        }
+                    }
+                    KeyCode::Esc if app.cancel_transfer() => {}
+                    KeyCode::Esc if !app.marked.is_empty() => app.clear_marks(),
+                    KeyCode::Esc if !app.search_query.is_empty() => app.clear_search(),
+                    KeyCode::Char('#') => {
+                        app.cycle_tag_filter();
+                        if app.tag_filter.is_none() && app.all_tags().is_empty() {
+                            app.show_error("No tags defined");
                         }
                     }
+                    KeyCode::Char('c') => connect_selected(terminal, &mut app, false)?,
+                    KeyCode::Char('C') => connect_selected(terminal, &mut app, true)?,
                     KeyCode::Char('k') => {
                         if let Err(e) = app.select_key_file() {
                             app.show_error(e.to_string());
@@ -140,68 +328,60 @@ fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut app: App) -> R
                             }
                         }
                     }
-                    KeyCode::Char('t') => {
-                        if let Some(idx) = app.selected_connection {
-                            match app.test_connection(idx) {
-                                Ok(_) => app.show_error("Connection test successful!"),
-                                Err(e) => match e {
-                                    AppError::ConnectionFailed(msg) => {
-                                        app.show_error(format!("Connection test failed: {}", msg));
-                                    }
-                                    AppError::AuthenticationFailed(msg) => {
-                                        app.show_error(format!("Authentication test failed: {}", msg));
-                                    }
-                                    AppError::NoConnectionSelected => {
-                                        app.show_error("No connection selected");
-                                    }
-                                },
-                            }
+                    KeyCode::Char('t') if !app.marked.is_empty() => {
+                        if let Err(e) = app.start_test_run(app.marked_connections()) {
+                            app.show_error(e);
+                        }
+                    }
+                    KeyCode::Char('t') => test_selected(&mut app),
+                    KeyCode::Char('+') => {
+                        if app.marked.is_empty() {
+                            app.show_error("Mark connections with Space first");
                         } else {
-                            app.show_error("No connection selected");
+                            app.start_prompt(PromptKind::TagMarked);
                         }
                     }
                     KeyCode::Char('s') => {
                         app.input_mode = InputMode::Settings;
                     }
-                    KeyCode::Enter => {
-                        if let Some(idx) = app.selected_connection {
-                            match app.test_connection(idx) {
-                                Ok(_) => {
-                                    match app.execute_ssh() {
-                                        Ok(needs_redraw) => {
-                                            if needs_redraw {
-                                                terminal.clear()?;
-                                                terminal.draw(|f| ui(f, &app))?;
-                                            }
-                                        }
-                                        Err(e) => {
-                                            app.show_error(format!("Failed to execute SSH: {}", e));
-                                        }
-                                    }
-                                }
-                                Err(e) => match e {
-                                    AppError::ConnectionFailed(msg) => {
-                                        app.show_error(format!("Connection test failed: {}", msg));
-                                    }
-                                    AppError::AuthenticationFailed(msg) => {
-                                        app.show_error(format!("Authentication test failed: {}", msg));
-                                    }
-                                    AppError::NoConnectionSelected => {
-                                        app.show_error("No connection selected");
-                                    }
-                                },
-                            }
-                        } else {
-                            app.show_error("No connection selected");
+                    KeyCode::Char('T') => {
+                        if let Err(e) = app.start_test_all() {
+                            app.show_error(e);
                         }
                     }
+                    KeyCode::Char(' ') if app.toggle_selected_group() => {}
+                    KeyCode::Char(' ') => {
+                        app.toggle_mark();
+                    }
+                    KeyCode::Char('P') => {
+                        app.toggle_privacy_mode();
+                    }
+                    KeyCode::Char('m') => {
+                        if let Err(e) = app.show_message_log() {
+                            app.show_error(e);
+                        }
+                    }
+                    KeyCode::Enter if app.toggle_selected_group() => {}
+                    KeyCode::Enter => connect_selected(terminal, &mut app, false)?,
                     _ => {}
                 },
                 InputMode::Adding | InputMode::Editing => match key.code {
-                    KeyCode::Esc => app.input_mode = InputMode::Normal,
+                    KeyCode::Esc => {
+                        app.form_state.forget_secrets();
+                        app.input_mode = InputMode::Normal;
+                    }
                     KeyCode::Tab => app.next_field(),
                     KeyCode::BackTab => app.previous_field(),
                     KeyCode::Backspace => app.delete_char(),
+                    KeyCode::Enter
+                        if key.modifiers.contains(KeyModifiers::ALT) && app.form_state.active_field == NOTES_FIELD =>
+                    {
+                        app.add_char('\n');
+                    }
+                    KeyCode::Enter if app.form_needs_master_password() => {
+                        let editing = app.input_mode == InputMode::Editing;
+                        app.start_prompt(PromptKind::MasterPassword(AfterUnlock::SaveForm { editing }));
+                    }
                     KeyCode::Enter => {
                         let result = match app.input_mode {
                             InputMode::Adding => app.save_connection(),
@@ -212,38 +392,293 @@ fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut app: App) -> R
                             app.show_error(e);
                         }
                     }
+                    KeyCode::Char('r')
+                        if key.modifiers.contains(KeyModifiers::CONTROL) && matches!(app.form_state.active_field, 4 | 5) =>
+                    {
+                        app.form_state.revealed = !app.form_state.revealed;
+                    }
+                    KeyCode::Char('o')
+                        if key.modifiers.contains(KeyModifiers::CONTROL) && app.form_state.active_field == 6 =>
+                    {
+                        app.select_certificate_file();
+                    }
+                    KeyCode::Char(' ') if app.form_state.active_is_check() => app.form_state.toggle_active(),
                     KeyCode::Char(c) => app.add_char(c),
-                    KeyCode::Right => {
-                        if app.form_state.active_field == 5 {
-                            app.select_ssh_key(1)
+                    KeyCode::Right if app.form_state.active_field == 5 => app.select_ssh_key(1),
+                    KeyCode::Left if app.form_state.active_field == 5 => app.select_ssh_key(-1),
+                    _ => {}
+                },
+                InputMode::Search => match key.code {
+                    KeyCode::Esc => app.clear_search(),
+                    KeyCode::Enter => {
+                        app.input_mode = InputMode::Normal;
+                        if app.visible_connections().is_empty() {
+                            app.show_error("No matching connections");
                         }
-                    },
-                    KeyCode::Left => {
-                        if app.form_state.active_field == 5 {
-                            app.select_ssh_key(-1)
+                    }
+                    KeyCode::Tab => app.cycle_match_mode(),
+                    KeyCode::Backspace => app.pop_search_char(),
+                    KeyCode::Up => app.select_previous(),
+                    KeyCode::Down => app.select_next(),
+                    KeyCode::Char(c) => app.push_search_char(c),
+                    _ => {}
+                },
+                InputMode::DualBrowser => match key.code {
+                    KeyCode::Esc if app.cancel_copies() => app.show_error("Cancelled the transfers"),
+                    KeyCode::Esc => {
+                        app.dual_browser = None;
+                        app.input_mode = InputMode::Normal;
+                    }
+                    KeyCode::Tab => {
+                        if let Some(dual) = &mut app.dual_browser {
+                            dual.toggle_focus();
                         }
-                    },
+                    }
+                    KeyCode::F(5) | KeyCode::F(6) => {
+                        if let Err(e) = app.queue_copy(key.code == KeyCode::F(6)) {
+                            app.show_error(e);
+                        }
+                    }
+                    KeyCode::Char('r') if app.dual_browser.as_ref().is_some_and(|dual| dual.is_disconnected()) => {
+                        match app.reconnect_dual_browser() {
+                            Ok(()) => app.show_error("Reconnected"),
+                            Err(e) => app.show_error(format!("Could not reconnect: {}", e)),
+                        }
+                    }
+                    KeyCode::Char('r') => {
+                        if let Some(dual) = &mut app.dual_browser {
+                            dual.local.refresh_keeping_selection();
+                            dual.remote.refresh_keeping_selection();
+                        }
+                    }
+                    code => {
+                        if let Some(browser) = app.dual_browser.as_mut().and_then(|dual| dual.focused_mut()) {
+                            match code {
+                                KeyCode::Up => browser.move_up(),
+                                KeyCode::Down => browser.move_down(),
+                                KeyCode::Enter => {
+                                    browser.enter_directory();
+                                }
+                                KeyCode::Char('h') => browser.toggle_hidden(),
+                                _ => {}
+                            }
+                        }
+                    }
+                },
+                InputMode::Palette => match key.code {
+                    KeyCode::Esc => app.input_mode = InputMode::Normal,
+                    KeyCode::Enter => {
+                        app.input_mode = InputMode::Normal;
+                        match app.palette.chosen(app.match_mode) {
+                            Some(command) => replay = Some(KeyEvent::new(KeyCode::Char(command.key), KeyModifiers::NONE)),
+                            None => app.show_error("No matching command"),
+                        }
+                    }
+                    KeyCode::Tab => app.cycle_match_mode(),
+                    KeyCode::Backspace => app.palette.pop(),
+                    KeyCode::Up | KeyCode::Down => {
+                        let count = app.palette.matches(app.match_mode).map_or(0, |matches| matches.len());
+                        app.palette.select(if key.code == KeyCode::Up { -1 } else { 1 }, count);
+                    }
+                    KeyCode::Char(c) => app.palette.push(c),
                     _ => {}
                 },
-                InputMode::Settings => match key.code {
+                InputMode::Unlock => match key.code {
+                    KeyCode::Esc => return Ok(()),
+                    KeyCode::Enter => {
+                        let password = std::mem::take(&mut app.prompt_input);
+                        match app.unlock(&password) {
+                            Ok(()) => app.input_mode = InputMode::Normal,
+                            Err(e) => app.show_error(format!("{:#}", e)),
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        app.prompt_input.pop();
+                    }
+                    KeyCode::Char(c) => app.prompt_input.push(c),
+                    _ => {}
+                },
+                InputMode::Locked if app.vault.is_some() => match key.code {
+                    KeyCode::Esc => app.prompt_input.clear(),
+                    KeyCode::Enter => {
+                        let password = std::mem::take(&mut app.prompt_input);
+                        if let Err(e) = app.unlock_screen(&password) {
+                            app.show_error(format!("{:#}", e));
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        app.prompt_input.pop();
+                    }
+                    KeyCode::Char(c) => app.prompt_input.push(c),
+                    _ => {}
+                },
+                InputMode::Locked => {
+                    let _ = app.unlock_screen("");
+                }
+                InputMode::Details => {
+                    if matches!(key.code, KeyCode::Esc | KeyCode::Char('q' | 'i')) {
+                        app.input_mode = InputMode::Normal;
+                    }
+                }
+                InputMode::TextViewer(ViewerKind::Follow)
+                    if app.text_viewer.as_ref().is_some_and(|viewer| viewer.search_input.is_none())
+                        && matches!(key.code, KeyCode::Char('p' | 's' | 'r')) =>
+                {
+                    follow_key(&mut app, key.code);
+                }
+                InputMode::TextViewer(_) => {
+                    let action = app.text_viewer.as_mut().map(|viewer| viewer.handle_key(key));
+                    match action {
+                        Some(ViewerAction::Continue) => {}
+                        Some(ViewerAction::CopyAll) => {
+                            let text = app.text_viewer.as_ref().map(TextViewer::text).unwrap_or_default();
+                            match copy_to_clipboard(&text) {
+                                Ok(()) => app.show_error("Copied to the clipboard"),
+                                Err(e) => app.show_error(format!("Copy failed: {:#}", e)),
+                            }
+                        }
+                        Some(ViewerAction::Close) | None => app.close_text_viewer(),
+                    }
+                }
+                InputMode::Prompt(kind) => match key.code {
+                    KeyCode::Esc => app.cancel_prompt(kind),
+                    KeyCode::Enter => {
+                        app.submit_prompt(kind);
+                        match kind {
+                            PromptKind::DefaultPassphrase { connect: true, shell } => connect_selected(terminal, &mut app, shell)?,
+                            PromptKind::DefaultPassphrase { connect: false, .. } => test_selected(&mut app),
+                            PromptKind::ConnectionPassword(PasswordUse::Test) | PromptKind::KeyPassphrase(PasswordUse::Test) => {
+                                test_selected(&mut app)
+                            }
+                            PromptKind::ConnectionPassword(PasswordUse::Connect { shell })
+                            | PromptKind::KeyPassphrase(PasswordUse::Connect { shell }) => {
+                                connect_selected(terminal, &mut app, shell)?
+                            }
+                            PromptKind::ConnectionPassword(PasswordUse::Browse) | PromptKind::KeyPassphrase(PasswordUse::Browse) => {
+                                browse_selected(&mut app)
+                            }
+                            PromptKind::ConnectionPassword(PasswordUse::BrowseBoth) | PromptKind::KeyPassphrase(PasswordUse::BrowseBoth) => {
+                                browse_both_selected(&mut app)
+                            }
+                            PromptKind::KeyringEntry | PromptKind::DefaultUsername => {
+                                if let Err(e) = app.save_settings() {
+                                    app.show_error(format!("Failed to save settings: {}", e));
+                                }
+                            }
+                            PromptKind::TagMarked => {}
+                            PromptKind::FollowPath => follow_selected(&mut app),
+                            PromptKind::QuickConnect => quick_connect(terminal, &mut app)?,
+                            PromptKind::RunCommand => run_command_selected(terminal, &mut app)?,
+                            PromptKind::UploadDestination => upload_selected(&mut app),
+                            PromptKind::DownloadSource => {
+                                if let Err(e) = app.select_download_folder() {
+                                    app.show_error(e.to_string());
+                                }
+                            }
+                            PromptKind::NewMasterPassword { confirm: false } => {}
+                            PromptKind::NewMasterPassword { confirm: true } if app.vault.is_some() => {
+                                match app.save_connections() {
+                                    Ok(()) => app.show_error("Connections are now encrypted"),
+                                    Err(e) => app.show_error(format!("Failed to save connections: {}", e)),
+                                }
+                            }
+                            PromptKind::NewMasterPassword { confirm: true } => {}
+                            PromptKind::ExportPassphrase { .. } | PromptKind::ImportPassphrase => {}
+                            PromptKind::MasterPassword(_) if app.secrets_locked() => {}
+                            PromptKind::MasterPassword(AfterUnlock::Key(c)) => {
+                                replay = Some(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+                            }
+                            PromptKind::MasterPassword(AfterUnlock::SaveForm { .. }) => {
+                                replay = Some(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+                            }
+                            PromptKind::MasterPassword(AfterUnlock::Decrypt) => {
+                                app.toggle_encryption();
+                                if let Err(e) = app.save_settings() {
+                                    app.show_error(format!("Failed to save settings: {}", e));
+                                }
+                            }
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        app.prompt_input.pop();
+                    }
+                    KeyCode::Char(c) => app.prompt_input.push(c),
+                    _ => {}
+                },
+                InputMode::Settings if app.settings_tab == SettingsTab::General => match key.code {
                     KeyCode::Esc => app.input_mode = InputMode::Normal,
                     KeyCode::Tab => app.next_settings_tab(),
-                    KeyCode::Up => {
-                        if app.settings_selected_item > 0 {
-                            app.settings_selected_item -= 1;
+                    KeyCode::Up if app.settings_selected_item > 0 => {
+                        app.settings_selected_item -= 1;
+                    }
+                    KeyCode::Down if app.settings_selected_item + 1 < GENERAL_SETTINGS_COUNT => {
+                        app.settings_selected_item += 1;
+                    }
+                    KeyCode::Enter
+                        if app.settings_selected_item == 9
+                            && matches!(app.default_passphrase, PassphraseSource::Keyring(_)) =>
+                    {
+                        app.start_prompt(PromptKind::KeyringEntry);
+                    }
+                    KeyCode::Left | KeyCode::Right | KeyCode::Char('-') | KeyCode::Char('+') | KeyCode::Enter => {
+                        let delta = if matches!(key.code, KeyCode::Left | KeyCode::Char('-')) { -1 } else { 1 };
+                        app.adjust_general_setting(app.settings_selected_item, delta);
+                        if let Err(e) = app.save_settings() {
+                            app.show_error(format!("Failed to save settings: {}", e));
                         }
                     }
-                    KeyCode::Down => {
+                    _ => {}
+                },
+                InputMode::Settings => match key.code {
+                    KeyCode::Esc => app.input_mode = InputMode::Normal,
+                    KeyCode::Tab => app.next_settings_tab(),
+                    KeyCode::Up if app.settings_selected_item > 0 => {
+                        app.settings_selected_item -= 1;
+                    }
+                    KeyCode::Down if app.settings_selected_item + 1 < app.settings_key_rows() => {
                         app.settings_selected_item += 1;
                     }
-                    KeyCode::Char('d') => {
-                        if app.settings_selected_item >= 3 && app.settings_selected_item < app.ssh_keys.len() + 3 {
-                            let key_index = app.settings_selected_item - 3;
-                            app.remove_ssh_key(key_index);
+                    KeyCode::Char('i') => match app.import_ssh_config() {
+                        Ok(imported) => {
+                            if let Err(e) = app.save_connections() {
+                                app.show_error(format!("Failed to save connections: {}", e));
+                            } else if let Err(e) = app.save_additional_keys() {
+                                app.show_error(format!("Failed to save additional keys: {}", e));
+                            } else {
+                                app.show_error(format!("Imported {} connections from ~/.ssh/config", imported));
+                            }
+                        }
+                        Err(e) => app.show_error(format!("Import failed: {}", e)),
+                    },
+                    KeyCode::Char('x') => {
+                        if let Err(e) = app.select_export_folder() {
+                            app.show_error(e.to_string());
+                        }
+                    }
+                    KeyCode::Char('D') if app.selected_settings_key().is_some() => {
+                        app.confirm_action(ConfirmationMode::RemoveKeySource);
+                    }
+                    KeyCode::Char('d') if app.selected_settings_key().is_some() => {
+                        app.confirm_action(ConfirmationMode::RemoveKey);
+                    }
+                    KeyCode::Char('W') if app.selected_settings_key().is_some() => {
+                        app.confirm_action(ConfirmationMode::DeleteKeyFile);
+                    }
+                    KeyCode::Char('r') => match app.selected_trashed_key() {
+                        Some(index) => {
+                            app.restore_trashed_key(index);
                             if let Err(e) = app.save_additional_keys() {
                                 app.show_error(format!("Failed to save additional keys: {}", e));
                             }
                         }
+                        None => app.show_error("Select a key under Recently removed to restore it"),
+                    },
+                    KeyCode::Char('X') if !app.key_trash.is_empty() => {
+                        let emptied = app.empty_key_trash();
+                        match app.save_additional_keys() {
+                            Ok(()) => app.show_error(format!("Removed {} keys for good", emptied)),
+                            Err(e) => app.show_error(format!("Failed to save additional keys: {}", e)),
+                        }
                     }
                     KeyCode::Enter => {
                         match app.settings_selected_item {
@@ -253,6 +688,9 @@ fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut app: App) -> R
                             1 => if let Err(e) = app.select_key_folder() {
                                 app.show_error(e.to_string());
                             },
+                            row if Some(row) == app.key_trash_row() => {
+                                app.key_trash_collapsed = !app.key_trash_collapsed;
+                            }
                             _ => {}
                         }
                         if let Err(e) = app.save_additional_keys() {
@@ -262,9 +700,11 @@ fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut app: App) -> R
                     _ => {}
                 },
                 InputMode::FileBrowser(mode) => match key.code {
-                    KeyCode::Esc => {
-                        app.input_mode = InputMode::Settings;
-                        app.file_browser = None;
+                    KeyCode::Esc => app.close_file_browser(mode),
+                    KeyCode::Char('d') if mode == FileBrowserMode::Remote => {
+                        if let Err(e) = app.choose_download_folder() {
+                            app.show_error(e.to_string());
+                        }
                     }
                     KeyCode::Up => {
                         if let Some(browser) = &mut app.file_browser {
@@ -276,6 +716,16 @@ fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut app: App) -> R
                             browser.move_down();
                         }
                     }
+                    KeyCode::Char('h') => {
+                        if let Some(browser) = &mut app.file_browser {
+                            browser.toggle_hidden();
+                        }
+                    }
+                    KeyCode::Char('k') if mode == FileBrowserMode::SingleFile => {
+                        if let Some(browser) = &mut app.file_browser {
+                            browser.toggle_keys_only();
+                        }
+                    }
                     KeyCode::Enter => {
                         if let Some(browser) = &mut app.file_browser {
                             match mode {
@@ -285,7 +735,7 @@ fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut app: App) -> R
                                             browser.enter_directory();
                                         } else {
                                             if browser.is_valid_ssh_key(&path) {
-                                                app.add_key_path(path);
+                                                app.add_key_path(path, KeySource::Manual);
                                                 if let Err(e) = app.save_additional_keys() {
                                                     app.show_error(format!("Failed to save additional keys: {}", e));
                                                 }
@@ -311,8 +761,8 @@ fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut app: App) -> R
                                             }
                                             
                                             let added = valid_paths.len();
-                                            for path in valid_paths {
-                                                app.add_key_path(path);
+                                            for key_path in valid_paths {
+                                                app.add_key_path(key_path, KeySource::Folder(path.clone()));
                                             }
                                             
                                             if let Err(e) = app.save_additional_keys() {
@@ -321,9 +771,119 @@ fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut app: App) -> R
                                             app.show_error(format!("Added {} SSH keys from folder", added));
                                             app.input_mode = InputMode::Settings;
                                             app.file_browser = None;
-                                        } else if path.ends_with("..") {
+                                        } else if path.ends_with("..") || path.is_dir() {
                                             browser.enter_directory();
-                                        } else if path.is_dir() {
+                                        }
+                                    }
+                                }
+                                FileBrowserMode::Remote => {
+                                    if let Some(path) = browser.get_selected_path() {
+                                        if browser.is_dir(&path) {
+                                            browser.enter_directory();
+                                        } else {
+                                            download_browsed(&mut app, &App::download_dir());
+                                        }
+                                    }
+                                }
+                                FileBrowserMode::UploadSource => {
+                                    if let Some(path) = browser.get_selected_path() {
+                                        if path.is_dir() {
+                                            browser.enter_directory();
+                                        } else {
+                                            app.choose_upload_file(&path);
+                                        }
+                                    }
+                                }
+                                FileBrowserMode::DownloadFolder => {
+                                    if let Some(path) = browser.get_selected_path() {
+                                        if path == browser.current_path {
+                                            app.file_browser = None;
+                                            app.input_mode = mode.return_mode();
+                                            download_selected(&mut app, &path);
+                                        } else if path.ends_with("..") || path.is_dir() {
+                                            browser.enter_directory();
+                                        }
+                                    }
+                                }
+                                FileBrowserMode::DownloadTarget => {
+                                    if let Some(path) = browser.get_selected_path() {
+                                        if path == browser.current_path {
+                                            if let Err(e) = app.download_to(&path) {
+                                                app.show_error(format!("Download failed: {}", e));
+                                            }
+                                        } else if path.ends_with("..") || path.is_dir() {
+                                            browser.enter_directory();
+                                        }
+                                    }
+                                }
+                                FileBrowserMode::BackupConnections => {
+                                    if let Some(path) = browser.get_selected_path() {
+                                        if path == browser.current_path {
+                                            match app.export_backup(&path) {
+                                                Ok(target) => app.show_error(format!("Backed up to {}", target.display())),
+                                                Err(e) => app.show_error(format!("Backup failed: {:#}", e)),
+                                            }
+                                            app.input_mode = InputMode::Normal;
+                                            app.file_browser = None;
+                                        } else if path.ends_with("..") || path.is_dir() {
+                                            browser.enter_directory();
+                                        }
+                                    }
+                                }
+                                FileBrowserMode::ImportConnections => {
+                                    if let Some(path) = browser.get_selected_path() {
+                                        if path.is_dir() {
+                                            browser.enter_directory();
+                                        } else if app.start_encrypted_import(&path) {
+                                            app.file_browser = None;
+                                        } else {
+                                            match app.import_connections(&path) {
+                                                Ok((imported, skipped)) => app.show_error(format!(
+                                                    "Imported {}, skipped {} duplicates",
+                                                    imported, skipped
+                                                )),
+                                                Err(e) => app.show_error(format!("Import failed: {:#}", e)),
+                                            }
+                                            app.input_mode = InputMode::Normal;
+                                            app.file_browser = None;
+                                        }
+                                    }
+                                }
+                                FileBrowserMode::Certificate { .. } => {
+                                    if let Some(path) = browser.get_selected_path() {
+                                        if path.is_dir() {
+                                            browser.enter_directory();
+                                        } else {
+                                            app.form_state.cert_path = path.display().to_string();
+                                            app.input_mode = mode.return_mode();
+                                            app.file_browser = None;
+                                        }
+                                    }
+                                }
+                                FileBrowserMode::ExportEncrypted => {
+                                    if let Some(path) = browser.get_selected_path() {
+                                        if path == browser.current_path {
+                                            app.file_browser = None;
+                                            app.start_encrypted_export(&path);
+                                        } else if path.ends_with("..") || path.is_dir() {
+                                            browser.enter_directory();
+                                        }
+                                    }
+                                }
+                                FileBrowserMode::ExportSshConfig => {
+                                    if let Some(path) = browser.get_selected_path() {
+                                        if path == browser.current_path {
+                                            match app.export_ssh_config(&path) {
+                                                Ok(target) => app.show_error(format!(
+                                                    "Exported {} connections to {}",
+                                                    app.connections.len(),
+                                                    target.display()
+                                                )),
+                                                Err(e) => app.show_error(format!("Export failed: {}", e)),
+                                            }
+                                            app.input_mode = InputMode::Settings;
+                                            app.file_browser = None;
+                                        } else if path.ends_with("..") || path.is_dir() {
                                             browser.enter_directory();
                                         }
                                     }
@@ -333,27 +893,341 @@ fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut app: App) -> R
                     }
                     _ => {}
                 },
-                InputMode::Confirmation(_mode) => match key.code {
+                InputMode::Confirmation(mode) => match key.code {
                     KeyCode::Esc => app.cancel_confirmation(),
                     KeyCode::Left | KeyCode::Right => app.toggle_confirmation_selection(),
                     KeyCode::Enter => {
-                        if app.confirmation_selected {
-                            if let Err(e) = app.perform_confirmed_action() {
-                                app.show_error(e);
+                        if let ConfirmationMode::Bootstrap { shell } = mode {
+                            app.input_mode = mode.return_mode();
+                            if app.confirmation_selected {
+                                bootstrap_selected(terminal, &mut app)?;
+                            }
+                            launch_ssh(terminal, &mut app, shell)?;
+                        } else if let ConfirmationMode::TrustHostKey { connect, shell } = mode {
+                            app.input_mode = mode.return_mode();
+                            if app.confirmation_selected {
+                                match app.trust_pending_host_key() {
+                                    Ok(_) if connect => connect_selected(terminal, &mut app, shell)?,
+                                    Ok(_) => test_selected(&mut app),
+                                    Err(e) => app.show_error(format!("Could not save the host key: {:#}", e)),
+                                }
+                            } else {
+                                app.pending_host_key = None;
+                            }
+                        } else if let ConfirmationMode::OverwriteDownload { .. } = mode {
+                            app.input_mode = mode.return_mode();
+                            if app.confirmation_selected {
+                                if let Err(e) = app.confirm_download() {
+                                    app.show_error(format!("Download failed: {}", e));
+                                }
+                            } else {
+                                app.pending_download = None;
+                            }
+                        } else if let ConfirmationMode::FixKeyPermissions { connect, shell } = mode {
+                            app.input_mode = mode.return_mode();
+                            if app.confirmation_selected {
+                                match app.fix_pending_key_permissions() {
+                                    Ok(()) if connect => connect_selected(terminal, &mut app, shell)?,
+                                    Ok(()) => test_selected(&mut app),
+                                    Err(e) => app.show_error(format!("{:#}", e)),
+                                }
                             } else {
-                                app.save_connections()?;
+                                app.pending_key_permissions = None;
                             }
+                        } else {
+                            if app.confirmation_selected {
+                                if let Err(e) = app.perform_confirmed_action() {
+                                    app.show_error(e);
+                                } else if matches!(mode, ConfirmationMode::RemoveKeySource | ConfirmationMode::RemoveKey) {
+                                    if let Err(e) = app.save_additional_keys() {
+                                        app.show_error(format!("Failed to save additional keys: {}", e));
+                                    }
+                                } else if mode == ConfirmationMode::DeleteKeyFile {
+                                    if let Err(e) = app.save_additional_keys() {
+                                        app.show_error(format!("Failed to save additional keys: {}", e));
+                                    }
+                                    app.save_connections()?;
+                                } else {
+                                    app.save_connections()?;
+                                }
+                            }
+                            app.input_mode = mode.return_mode();
                         }
-                        app.input_mode = InputMode::Normal;
                     }
                     _ => {}
                 },
             }
+
+            // An entered password lasts for one attempt, which may go on
+            // through a host key or bootstrap dialog.
+            if !matches!(app.input_mode, InputMode::Confirmation(_)) {
+                app.forget_entered_password();
+            }
+
+            // Announce mode changes on the message line, unless the action
+            // itself reported something.
+            let mode_after = mode_description(&app);
+            if mode_after != mode_before && app.error_message.is_none() {
+                app.announce(mode_after);
+            }
         }
     }
 }
 
-fn ui(f: &mut Frame, app: &App) {
+/// Shows the scenario's connections and plays its outcomes instead of
+/// reaching real hosts. Nothing is saved while simulating.
+fn start_simulation(app: &mut App, scenario: Scenario) {
+    app.connections = scenario.connections.clone();
+    app.simulation = Some(Arc::new(scenario));
+    app.persist = false;
+}
+
+/// Tests the selected connection and, if that succeeds, hands the terminal
+/// to ssh.
+fn connect_selected(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App, shell: bool) -> Result<()> {
+    let Some(idx) = app.selected_connection else {
+        app.show_error("No connection selected");
+        return Ok(());
+    };
+    match app.test_connection(idx) {
+        Ok(_) => {
+            if app.wants_bootstrap_check(idx, shell) {
+                show_progress(terminal, app, "Checking whether the host is bootstrapped…")?;
+                match app.bootstrap_check(idx) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        app.confirm_action(ConfirmationMode::Bootstrap { shell });
+                        return Ok(());
+                    }
+                    Err(e) => app.show_error(format!("Bootstrap check failed, connecting without it: {}", e)),
+                }
+            }
+            launch_ssh(terminal, app, shell)?;
+        }
+        Err(AppError::PassphraseRequired) => app.start_prompt(app.passphrase_prompt(idx, PasswordUse::Connect { shell })),
+        Err(AppError::PasswordRequired) => app.start_prompt(PromptKind::ConnectionPassword(PasswordUse::Connect { shell })),
+        Err(AppError::UnknownHostKey(key)) => app.ask_to_trust_host_key(key, true, shell),
+        Err(AppError::KeyPermissions(e)) => app.ask_to_fix_key_permissions(e, true, shell),
+        Err(e) => app.show_error(test_failure_message(&e)),
+    }
+    Ok(())
+}
+
+/// Hands the terminal to ssh for the selected connection.
+fn launch_ssh(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App, shell: bool) -> Result<()> {
+    let result = app.execute_ssh(shell);
+    if let Err(e) = app.save_connections() {
+        app.show_error(format!("Failed to save connections: {}", e));
+    }
+    match result {
+        Ok(needs_redraw) => {
+            if needs_redraw {
+                terminal.clear()?;
+                terminal.draw(|f| ui(f, app))?;
+            } else if app.simulation.is_some() {
+                app.show_error("Simulated connection succeeded; no ssh was started");
+            }
+        }
+        Err(e) => {
+            app.show_error(format!("Failed to execute SSH: {}", e));
+        }
+    }
+    Ok(())
+}
+
+/// Hands the terminal to ssh for the quick connect target, which is not
+/// saved.
+fn quick_connect(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> Result<()> {
+    let target = app.quick_connect_target.clone();
+    if target.is_empty() {
+        return Ok(());
+    }
+    match app.quick_connect(&target) {
+        Ok(_) => {
+            terminal.clear()?;
+            terminal.draw(|f| ui(f, app))?;
+        }
+        Err(e) => app.show_error(format!("Quick connect failed: {}", e)),
+    }
+    Ok(())
+}
+
+/// Uploads and runs the selected connection's bootstrap script. The
+/// outcome goes to the message log, since ssh takes over the screen next.
+fn bootstrap_selected(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> Result<()> {
+    let Some(idx) = app.selected_connection else {
+        return Ok(());
+    };
+    show_progress(terminal, app, "Uploading and running the bootstrap script…")?;
+    match app.run_bootstrap(idx) {
+        Ok(last_line) if last_line.is_empty() => app.show_error("Bootstrap finished"),
+        Ok(last_line) => app.show_error(format!("Bootstrap finished: {}", last_line)),
+        Err(e) => app.show_error(format!("Bootstrap failed, connecting anyway: {}", e)),
+    }
+    Ok(())
+}
+
+/// Shows `message` and redraws before a blocking step.
+fn show_progress(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App, message: &str) -> Result<()> {
+    app.show_error(message);
+    terminal.draw(|f| ui(f, app))?;
+    Ok(())
+}
+
+/// Follows the path just entered on the selected connection.
+fn follow_selected(app: &mut App) {
+    let Some(idx) = app.selected_connection else {
+        app.show_error("No connection selected");
+        return;
+    };
+    match app.start_follow(idx) {
+        Ok(()) => {}
+        Err(AppError::PassphraseRequired) => app.start_prompt(PromptKind::DefaultPassphrase { connect: false, shell: false }),
+        Err(e) => app.show_error(e.to_string()),
+    }
+}
+
+/// Runs the command just entered on the selected connection.
+fn run_command_selected(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> Result<()> {
+    let Some(idx) = app.selected_connection else {
+        app.show_error("No connection selected");
+        return Ok(());
+    };
+    show_progress(terminal, app, "Running the command…")?;
+    match app.run_command(idx) {
+        Ok(()) => app.clear_error(),
+        Err(AppError::PassphraseRequired) => app.start_prompt(PromptKind::DefaultPassphrase { connect: false, shell: false }),
+        Err(e) => app.show_error(format!("Command failed: {}", e)),
+    }
+    Ok(())
+}
+
+/// Uploads the chosen file to the destination just entered.
+fn upload_selected(app: &mut App) {
+    let Some(idx) = app.selected_connection else {
+        app.show_error("No connection selected");
+        return;
+    };
+    match app.start_upload(idx) {
+        Ok(()) => {}
+        Err(AppError::PassphraseRequired) => app.start_prompt(PromptKind::DefaultPassphrase { connect: false, shell: false }),
+        Err(e) => app.show_error(format!("Upload failed: {}", e)),
+    }
+}
+
+/// Downloads the remote file just entered into `dir`.
+fn download_selected(app: &mut App, dir: &Path) {
+    let Some(idx) = app.selected_connection else {
+        app.show_error("No connection selected");
+        return;
+    };
+    match app.download_entered(idx, dir) {
+        Ok(()) => {}
+        Err(AppError::PassphraseRequired) => app.start_prompt(PromptKind::DefaultPassphrase { connect: false, shell: false }),
+        Err(e) => app.show_error(format!("Download failed: {}", e)),
+    }
+}
+
+/// Downloads the file selected in the remote browser into `dir`.
+fn download_browsed(app: &mut App, dir: &Path) {
+    if let Err(e) = app.download_browsed(dir) {
+        app.show_error(format!("Download failed: {}", e));
+    }
+}
+
+/// The Follow viewer's own keys: pause, save to a file and reconnect.
+fn follow_key(app: &mut App, code: KeyCode) {
+    let Some(follow) = app.follow.as_mut() else {
+        return;
+    };
+    match code {
+        KeyCode::Char('p') => follow.paused = !follow.paused,
+        KeyCode::Char('s') => match follow.toggle_output(App::download_dir()) {
+            Ok(Some(path)) => app.show_error(format!("Writing lines to {}", path.display())),
+            Ok(None) => app.show_error("Stopped writing to the file"),
+            Err(e) => app.show_error(format!("{:#}", e)),
+        },
+        KeyCode::Char('r') if follow.ended.is_some() => {
+            if let Err(e) = app.restart_follow() {
+                app.show_error(e.to_string());
+            }
+        }
+        _ => {}
+    }
+}
+
+fn test_selected(app: &mut App) {
+    let Some(idx) = app.selected_connection else {
+        app.show_error("No connection selected");
+        return;
+    };
+    match app.test_connection(idx) {
+        Ok(_) => app.show_error("Connection test successful!"),
+        Err(AppError::PassphraseRequired) => app.start_prompt(app.passphrase_prompt(idx, PasswordUse::Test)),
+        Err(AppError::PasswordRequired) => app.start_prompt(PromptKind::ConnectionPassword(PasswordUse::Test)),
+        Err(AppError::UnknownHostKey(key)) => app.ask_to_trust_host_key(key, false, false),
+        Err(AppError::KeyPermissions(e)) => app.ask_to_fix_key_permissions(e, false, false),
+        Err(e) => app.show_error(test_failure_message(&e)),
+    }
+}
+
+/// Copies the ssh command line of the selected connection.
+fn copy_command_selected(app: &mut App) {
+    let Some(conn) = app.selected_connection.and_then(|idx| app.connections.get(idx)) else {
+        app.show_error("No connection selected");
+        return;
+    };
+    let result = app.command_to_copy(conn).map_err(anyhow::Error::msg).and_then(|command| copy_to_clipboard(&command));
+    match result {
+        Ok(()) => app.show_error("Copied ssh command"),
+        Err(e) => app.show_error(format!("Could not copy the ssh command: {}", e)),
+    }
+}
+
+fn browse_selected(app: &mut App) {
+    let Some(idx) = app.selected_connection else {
+        app.show_error("No connection selected");
+        return;
+    };
+    match app.open_remote_browser(idx) {
+        Ok(()) => {}
+        Err(AppError::PassphraseRequired) => app.start_prompt(app.passphrase_prompt(idx, PasswordUse::Browse)),
+        Err(AppError::PasswordRequired) => app.start_prompt(PromptKind::ConnectionPassword(PasswordUse::Browse)),
+        Err(AppError::UnknownHostKey(key)) => app.ask_to_trust_host_key(key, false, false),
+        Err(AppError::KeyPermissions(e)) => app.ask_to_fix_key_permissions(e, false, false),
+        Err(e) => app.show_error(test_failure_message(&e)),
+    }
+}
+
+fn browse_both_selected(app: &mut App) {
+    let Some(idx) = app.selected_connection else {
+        app.show_error("No connection selected");
+        return;
+    };
+    match app.open_dual_browser(idx) {
+        Ok(()) => {}
+        Err(AppError::PassphraseRequired) => app.start_prompt(app.passphrase_prompt(idx, PasswordUse::BrowseBoth)),
+        Err(AppError::UnknownHostKey(key)) => app.ask_to_trust_host_key(key, false, false),
+        Err(AppError::KeyPermissions(e)) => app.ask_to_fix_key_permissions(e, false, false),
+        Err(e) => app.show_error(test_failure_message(&e)),
+    }
+}
+
+fn test_failure_message(e: &AppError) -> String {
+    match e {
+        AppError::ConnectionFailed(msg) => format!("Connection test failed: {}", msg),
+        AppError::AuthenticationFailed(msg) => format!("Authentication test failed: {}", msg),
+        AppError::ResolutionFailed(msg) => format!("Connection test failed: {}", msg),
+        AppError::TimedOut(msg) => format!("Connection test failed: {}", msg),
+        AppError::ProxyFailed(msg) => format!("Connection test failed at the proxy: {}", msg),
+        AppError::UnknownHostKey(_) | AppError::HostKeyChanged(..) | AppError::KeyPermissions(_) => e.to_string(),
+        AppError::PassphraseRequired => "Authentication test failed: key passphrase required".to_string(),
+        AppError::PasswordRequired => "Authentication test failed: password required".to_string(),
+        AppError::NoConnectionSelected => "No connection selected".to_string(),
+    }
+}
+
+fn ui(f: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -364,129 +1238,543 @@ fn ui(f: &mut Frame, app: &App) {
         ])
         .split(f.area());
 
-    let title = Paragraph::new("Peroxide - SSH Connection Manager")
+    let mut title = format!("Peroxide - SSH Connection Manager — {}", mode_description(app));
+    if app.privacy_mode {
+        title.push_str(" [privacy mode]");
+    }
+    if app.simulation.is_some() {
+        title.push_str(" [simulation]");
+    }
+    let title = Paragraph::new(text::truncate(&title, chunks[0].width.saturating_sub(2) as usize))
         .alignment(Alignment::Center)
-        .block(Block::default().borders(Borders::ALL));
+        .block(app.theme.block());
     f.render_widget(title, chunks[0]);
 
-    match &app.input_mode {
-        InputMode::Normal => render_connections(f, app, chunks[1]),
+    match app.input_mode {
+        InputMode::Normal | InputMode::Search => render_connections_with_details(f, app, chunks[1]),
         InputMode::Adding | InputMode::Editing => render_form(f, app, chunks[1]),
         InputMode::Settings => render_settings(f, app, chunks[1]),
         InputMode::FileBrowser(_mode) => render_file_browser(f, app, chunks[1]),
-        InputMode::Confirmation(mode) => render_confirmation(f, app, chunks[1], mode),
+        InputMode::Confirmation(mode) => render_confirmation(f, app, chunks[1], &mode),
+        InputMode::Prompt(kind) => {
+            match kind.return_mode() {
+                InputMode::Settings => render_settings(f, app, chunks[1]),
+                _ => render_connections(f, app, chunks[1]),
+            }
+            render_prompt(f, app, chunks[1], kind);
+        }
+        InputMode::Unlock => render_unlock(f, app, chunks[1]),
+        InputMode::Locked => render_locked(f, app, chunks[1]),
+        InputMode::Details => {
+            render_connections(f, app, chunks[1]);
+            render_details(f, app, chunks[1]);
+        }
+        InputMode::Palette => {
+            render_connections(f, app, chunks[1]);
+            render_palette(f, app, chunks[1]);
+        }
+        InputMode::DualBrowser => render_dual_browser(f, app, chunks[1]),
+        InputMode::TextViewer(kind) => {
+            match kind.return_mode() {
+                InputMode::Settings => render_settings(f, app, chunks[1]),
+                _ => render_connections(f, app, chunks[1]),
+            }
+            render_text_viewer(f, app, chunks[1]);
+        }
     }
 
     let help = match &app.input_mode {
-        InputMode::Normal => "q: Quit | a: Add | e: Edit | d: Delete | y: Duplicate | s: Settings | ↑↓: Navigate",
-        InputMode::Adding => "Esc: Cancel | Tab: Next Field | Enter: Save | ←→: Select SSH Key",
-        InputMode::Editing => "Esc: Cancel | Tab: Next Field | Enter: Update | ←→: Select SSH Key",
-        InputMode::Settings => "Esc: Back | Tab: Switch Tab | ↑↓: Navigate | Enter: Select | d: Delete Key",
-        InputMode::FileBrowser(_mode) => "Esc: Cancel | ↑↓: Navigate | Enter: Select/Enter Directory",
+        InputMode::Normal => "q: Quit | a: Add | e: Edit | d: Delete | c/C: Connect/Shell | g: Quick Connect | u: Undo | b: Browse Files | B: Local/Remote Browser | x: Run Command | U/D: Upload/Download File | F: Follow Remote File | i: Details | v: Host Key | p: Copy ssh Command | h/H: Archive/Show Archived | E/I: Backup/Import | X: Encrypted Export | y: Duplicate | s: Settings | T: Test All | /: Search (Esc clears) | Ctrl+P: Command Palette | #: Tag Filter | Space: Mark/Fold Group | +: Tag Marked | P: Privacy | m: Messages | ↑↓: Navigate | Shift+↑↓: Move",
+        InputMode::Search => "Esc: Clear | Enter: Keep Filter | Tab: Match Mode | ↑↓: Navigate",
+        InputMode::Palette => "Esc: Close | Enter: Run | Tab: Match Mode | ↑↓: Navigate",
+        InputMode::DualBrowser if app.dual_browser.as_ref().is_some_and(|dual| dual.is_disconnected()) => {
+            "Esc: Close | Tab: Switch Pane | r: Reconnect | ↑↓: Navigate | Enter: Enter Directory | h: Show/Hide Hidden"
+        }
+        InputMode::DualBrowser => "Esc: Cancel Transfers/Close | Tab: Switch Pane | F5: Copy | F6: Move | ↑↓: Navigate | Enter: Enter Directory | h: Show/Hide Hidden | r: Refresh",
+        InputMode::Adding => "Esc: Cancel | Tab: Next Field | Enter: Save | Space: Toggle Checkbox | ←→: Select SSH Key | Ctrl+R: Reveal Password | Ctrl+O: Browse Certificate",
+        InputMode::Editing => "Esc: Cancel | Tab: Next Field | Enter: Update | Space: Toggle Checkbox | ←→: Select SSH Key | Ctrl+R: Reveal Password | Ctrl+O: Browse Certificate",
+        InputMode::Settings if app.settings_tab == SettingsTab::General => "Esc: Back | Tab: Switch Tab | ↑↓: Navigate | ←→/Enter: Change",
+        InputMode::Settings => "Esc: Back | Tab: Switch Tab | ↑↓: Navigate | Enter: Select | d: Remove Key | D: Remove Keys From Source | W: Delete Key File | r: Restore | X: Empty Trash | i: Import ~/.ssh/config | x: Export SSH Config",
+        InputMode::FileBrowser(FileBrowserMode::Remote) => "Esc: Close | ↑↓: Navigate | Enter: Download File/Enter Directory | d: Download To… | h: Show/Hide Hidden",
+        InputMode::FileBrowser(FileBrowserMode::DownloadTarget) => "Esc: Back | ↑↓: Navigate | Enter: Download Here (on .)/Enter Directory | h: Show/Hide Hidden",
+        InputMode::FileBrowser(FileBrowserMode::SingleFile) => "Esc: Cancel | ↑↓: Navigate | Enter: Select/Enter Directory | h: Show/Hide Hidden | k: Key Files Only",
+        InputMode::FileBrowser(_mode) => "Esc: Cancel | ↑↓: Navigate | Enter: Select/Enter Directory | h: Show/Hide Hidden",
         InputMode::Confirmation(_) => "Esc: Cancel | ←→: Navigate | Enter: Confirm Selection",
+        InputMode::Prompt(_) => "Esc: Cancel | Enter: Submit",
+        InputMode::Unlock => "Esc: Quit | Enter: Unlock",
+        InputMode::Locked if app.vault.is_some() => "Esc: Clear | Enter: Unlock",
+        InputMode::Locked => "Any key: Unlock",
+        InputMode::Details => "Esc/q/i: Close",
+        InputMode::TextViewer(_) if app.text_viewer.as_ref().is_some_and(|viewer| viewer.search_input.is_some()) => {
+            "Esc: Cancel Search | Enter: Search"
+        }
+        InputMode::TextViewer(ViewerKind::Follow) => "Esc/q: Close | p: Pause | s: Save to File | r: Reconnect | ↑↓/PgUp/PgDn: Scroll | G: Follow | w: Wrap | /: Search | n/N: Next/Previous Match | y: Copy All",
+        InputMode::TextViewer(_) => "Esc/q: Close | ↑↓/PgUp/PgDn: Scroll | g/G: Top/Bottom | w: Wrap | /: Search | n/N: Next/Previous Match | y: Copy All",
     };
 
+    let mut help_block = app.theme.block().title(mode_description(app));
+    if matches!(app.input_mode, InputMode::Normal | InputMode::Search) {
+        help_block = help_block.title_top(Line::from(list_position(app)).right_aligned());
+    }
     let help = Paragraph::new(help)
         .alignment(Alignment::Center)
-        .block(Block::default().borders(Borders::ALL));
+        .block(help_block);
     f.render_widget(help, chunks[2]);
 
     if let Some(error) = &app.error_message {
         let error_message = Paragraph::new(error.as_str())
-            .style(Style::default().fg(Color::Red))
+            .style(Style::default().fg(app.theme.error))
             .alignment(Alignment::Center);
         f.render_widget(error_message, chunks[3]);
+    } else if let Some(transfer) = &app.transfer {
+        let progress = match transfer.percent() {
+            Some(percent) => format!("{}%", percent),
+            None => format!("{} bytes", transfer.sent),
+        };
+        let status = match transfer.direction {
+            _ if transfer.is_cancelled() => "Cancelling the transfer…".to_string(),
+            TransferDirection::Upload => format!(
+                "Uploading {} to {}:{} — {} (Esc cancels)",
+                transfer.local.file_name().unwrap_or_default().to_string_lossy(),
+                transfer.connection,
+                transfer.remote,
+                progress
+            ),
+            TransferDirection::Download => format!(
+                "Downloading {}:{} to {} — {} (Esc cancels)",
+                transfer.connection,
+                transfer.remote,
+                transfer.local.display(),
+                progress
+            ),
+        };
+        let progress = Paragraph::new(status).style(app.theme.dim()).alignment(Alignment::Center);
+        f.render_widget(progress, chunks[3]);
+    }
+
+    if app.privacy_mode {
+        mask_sensitive(f.buffer_mut(), &app.sensitive_strings());
+    }
+}
+
+/// Where the selection is in the list, as `3/27`. When a search, tag
+/// filter or hidden archive leaves some connections out, the total follows:
+/// `2/5 of 27`.
+fn list_position(app: &App) -> String {
+    let visible = app.visible_connections();
+    let position = app
+        .selected_connection
+        .and_then(|selected| visible.iter().position(|&idx| idx == selected))
+        .map_or_else(|| "–".to_string(), |pos| (pos + 1).to_string());
+    if visible.len() < app.connections.len() {
+        format!(" {}/{} of {} ", position, visible.len(), app.connections.len())
+    } else {
+        format!(" {}/{} ", position, visible.len())
+    }
+}
+
+/// Masks hosts, usernames and IP-looking text in the finished frame, so no
+/// render function can leak them while privacy mode is on.
+fn mask_sensitive(buf: &mut Buffer, needles: &[String]) {
+    let area = buf.area;
+    for y in area.top()..area.bottom() {
+        let mut line = String::new();
+        let mut columns = Vec::new();
+        for x in area.left()..area.right() {
+            let symbol = buf[(x, y)].symbol();
+            columns.extend(std::iter::repeat_n(x, symbol.len()));
+            line.push_str(symbol);
+        }
+
+        let mut ranges = Vec::new();
+        for needle in needles {
+            let mut from = 0;
+            while let Some(pos) = line[from..].find(needle.as_str()) {
+                let start = from + pos;
+                let end = start + needle.len();
+                if is_word_boundary(&line, start, end) {
+                    ranges.push((start, end));
+                }
+                from = end;
+            }
+        }
+        ranges.extend(ip_like_ranges(&line));
+
+        for (start, end) in ranges {
+            let mut last = None;
+            for &x in &columns[start..end] {
+                if last != Some(x) {
+                    buf[(x, y)].set_symbol("•");
+                    last = Some(x);
+                }
+            }
+        }
+    }
+}
+
+fn is_word_boundary(line: &str, start: usize, end: usize) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '.');
+    let before = line[..start].chars().next_back();
+    let after = line[end..].chars().next();
+    !before.is_some_and(is_word) && !after.is_some_and(is_word)
+}
+
+fn ip_like_ranges(line: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let is_token = |b: u8| b.is_ascii_hexdigit() || b == b'.' || b == b':';
+        if !is_token(bytes[i]) || (i > 0 && is_token(bytes[i - 1])) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && is_token(bytes[i]) {
+            i += 1;
+        }
+        let token = line[start..i].trim_end_matches(['.', ':']);
+        if token.contains('.') {
+            let address = token.split(':').next().unwrap_or_default();
+            let is_ipv4 = address.split('.').count() == 4
+                && address.split('.').all(|part| (1..=3).contains(&part.len()) && part.bytes().all(|b| b.is_ascii_digit()));
+            if is_ipv4 {
+                ranges.push((start, start + address.len()));
+            }
+        } else if token.contains("::") || token.matches(':').count() >= 3 {
+            ranges.push((start, start + token.len()));
+        }
+    }
+    ranges
+}
+
+/// Widest the name column in the connection list is allowed to grow.
+const NAME_COLUMN_MAX: usize = 32;
+
+/// Shows the connection list, with the highlighted connection's notes in a
+/// side panel when it has any.
+/// The connection list with a details panel beside it while the highlighted
+/// connection has notes, a remote command or extra ssh options: the command
+/// it will run, then its notes.
+fn render_connections_with_details(f: &mut Frame, app: &mut App, area: Rect) {
+    let conn = app
+        .selected_connection
+        .filter(|_| app.selected_group.is_none())
+        .and_then(|idx| app.connections.get(idx))
+        .filter(|conn| {
+            conn.notes.is_some() || conn.remote_command.is_some() || conn.extra_args.is_some() || !conn.ssh_options.is_empty() || conn.host_key_policy.is_some()
+        });
+    let Some(conn) = conn.cloned() else {
+        render_connections(f, app, area);
+        return;
+    };
+    let conn = &conn;
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+        .split(area);
+    render_connections(f, app, chunks[0]);
+
+    let mut lines = Vec::new();
+    if conn.remote_command.is_some() || conn.extra_args.is_some() || !conn.ssh_options.is_empty() || conn.host_key_policy.is_some() {
+        lines.push(Line::from(Span::styled("Command", Style::default().add_modifier(Modifier::BOLD))));
+        match app.command_preview(conn) {
+            Ok(command) => lines.push(Line::from(command)),
+            Err(e) => lines.push(Line::from(Span::styled(e, Style::default().fg(app.theme.error)))),
+        }
+    }
+    if let Some(notes) = &conn.notes {
+        if !lines.is_empty() {
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled("Notes", Style::default().add_modifier(Modifier::BOLD))));
+        }
+        lines.extend(notes.lines().map(Line::from));
     }
+    let panel = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(app.theme.block().title("Details"));
+    f.render_widget(panel, chunks[1]);
 }
 
-fn render_connections(f: &mut Frame, app: &App, area: Rect) {
-    let items: Vec<ListItem> = app
-        .connections
+/// Renders through `app.connection_list`, so the scroll offset survives
+/// between frames and ratatui keeps the selection in view.
+fn render_connections(f: &mut Frame, app: &mut App, area: Rect) {
+    let rows = app.list_rows();
+    let grouped = rows.iter().any(|row| matches!(row, ListRow::Group { .. }));
+    // Inside the borders, minus the highlight symbol.
+    let inner_width = area.width.saturating_sub(4) as usize;
+    let name_width = rows
         .iter()
-        .map(|conn| {
+        .filter_map(|row| match row {
+            ListRow::Connection(idx) => Some(text::display_width(&app.connections[*idx].name)),
+            ListRow::Group { .. } => None,
+        })
+        .max()
+        .unwrap_or(0)
+        .min(NAME_COLUMN_MAX);
+    let security_keys: HashSet<usize> = rows
+        .iter()
+        .filter_map(|row| match row {
+            ListRow::Connection(idx) => Some(*idx),
+            ListRow::Group { .. } => None,
+        })
+        .filter(|&idx| {
+            let key_path = app.connections[idx].key_path.clone();
+            key_path.is_some_and(|path| app.is_security_key(&path))
+        })
+        .collect();
+    let items: Vec<ListItem> = rows
+        .iter()
+        .map(|row| {
+            let idx = match row {
+                ListRow::Group { name, count, collapsed } => {
+                    let marker = if *collapsed { "▶" } else { "▼" };
+                    let label = format!("{} {} ({})", marker, name, count);
+                    return ListItem::new(text::truncate(&label, inner_width))
+                        .style(Style::default().add_modifier(Modifier::BOLD));
+                }
+                ListRow::Connection(idx) => *idx,
+            };
+            let conn = &app.connections[idx];
+            let matched = app.connection_match(idx).unwrap_or_default();
             let auth_method = if conn.key_path.is_some() {
                 "🔑"
-            } else if conn.password.is_some() {
+            } else if conn.ask_password && !conn.has_password() {
+                "❔"
+            } else if conn.has_password() {
                 "🔒"
+            } else if conn.use_agent {
+                "🔑"
             } else {
                 "❌"
             };
 
             let status = match conn.last_connection_status {
+                _ if app.is_testing(idx) => "⏳",
                 Some(true) => "✅",
                 Some(false) => "❌",
                 None => "  ",
             };
             
-            ListItem::new(format!(
-                "{} {} {} ({}@{}:{})",
-                status, auth_method, conn.name, conn.username, conn.host, conn.port
-            ))
+            let indent = if grouped { "  " } else { "" };
+            let mark = match (app.marked.is_empty(), app.marked.contains(&idx)) {
+                (true, _) => "",
+                (false, true) => "[x] ",
+                (false, false) => "[ ] ",
+            };
+            let mut spans = vec![Span::raw(format!("{}{}{} {} ", indent, mark, status, auth_method))];
+            let name = text::truncate(&conn.name, name_width);
+            let kept = if name == conn.name { name.chars().count() } else { name.chars().count() - 1 };
+            let name_matches: Vec<usize> = matched.name.into_iter().filter(|&i| i < kept).collect();
+            let fill = name_width.saturating_sub(text::display_width(&name));
+            spans.extend(owned(highlight_matches(&name, &name_matches, app.theme.highlight)));
+            spans.push(Span::raw(format!("{} (", " ".repeat(fill))));
+            spans.extend(owned(highlight_matches(&conn.username, &matched.username, app.theme.highlight)));
+            spans.push(Span::raw("@"));
+            spans.extend(owned(highlight_matches(&conn.host, &matched.host, app.theme.highlight)));
+            spans.push(Span::raw(format!(":{})", conn.port)));
+            for tag in &conn.tags {
+                spans.push(Span::raw(" "));
+                spans.push(Span::styled(format!("[{}]", tag), Style::default().bg(app.theme.dim)));
+            }
+            if security_keys.contains(&idx) {
+                spans.push(Span::styled(" touch key", app.theme.dim()));
+            }
+            if conn.slow_link {
+                spans.push(Span::styled(" slow", app.theme.dim()));
+            }
+            if conn.archived {
+                spans.push(Span::styled(" archived", app.theme.dim()));
+            }
+            if conn.host_key_policy == Some(HostKeyPolicy::Off) {
+                spans.push(Span::styled(" host key unchecked", Style::default().fg(app.theme.error)));
+            }
+            let expired = app.is_expired(idx);
+            if expired {
+                spans.push(Span::styled(" expired", app.theme.dim()));
+            }
+            if app.show_use_count {
+                spans.push(Span::styled(format!(" ({})", conn.use_count), app.theme.dim()));
+            }
+            if let Some(last_connected) = conn.last_connected {
+                spans.push(Span::styled(
+                    format!(" · {}", app.format_timestamp(last_connected)),
+                    app.theme.dim(),
+                ));
+            }
+            let mut lines = vec![Line::from(spans)];
+            if let Some(note) = conn.notes.as_deref().and_then(|notes| notes.lines().next()) {
+                let note_indent = format!("{}      ", indent);
+                let note = text::truncate(note, inner_width.saturating_sub(note_indent.len()));
+                lines.push(Line::styled(format!("{}{}", note_indent, note), app.theme.dim()));
+            }
+            let item = ListItem::new(lines);
+            if expired {
+                item.style(app.theme.dim())
+            } else {
+                item
+            }
         })
         .collect();
 
+    let mut title = String::from("Connections");
+    if app.sort_order != SortOrder::Manual {
+        title.push_str(&format!(" [sort: {}]", app.sort_order.label()));
+    }
+    if let Some(tag) = &app.tag_filter {
+        title.push_str(&format!(" [tag: {}]", tag));
+    }
+    if app.show_archived {
+        title.push_str(" [showing archived]");
+    }
+    if !app.marked.is_empty() {
+        title.push_str(&format!(" [{} marked]", app.marked.len()));
+    }
+    if app.input_mode == InputMode::Search || !app.search_query.is_empty() {
+        title.push_str(&format!(" [/{} ({})]", app.search_query, app.match_mode.label()));
+        if let Some(error) = app.search_error() {
+            title.push_str(&format!(" - {}", error));
+        }
+    }
     let list = List::new(items)
-        .block(Block::default().title("Connections").borders(Borders::ALL))
-        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(app.theme.block().title(text::truncate(&title, area.width.saturating_sub(2) as usize)))
+        .highlight_style(app.theme.selected())
         .highlight_symbol("> ");
 
-    f.render_stateful_widget(
-        list,
-        area,
-        &mut ListState::default().with_selected(app.selected_connection),
-    );
+    let selected = app.selected_row(&rows);
+    app.connection_list.select(selected);
+    f.render_stateful_widget(list, area, &mut app.connection_list);
+}
+
+/// Copies borrowed span text, so the spans outlive what they were cut from.
+fn owned(spans: Vec<Span>) -> Vec<Span<'static>> {
+    spans
+        .into_iter()
+        .map(|span| Span::styled(span.content.into_owned(), span.style))
+        .collect()
+}
+
+/// Splits `text` into spans, emphasising the chars at `positions`.
+fn highlight_matches<'a>(text: &'a str, positions: &[usize], color: Color) -> Vec<Span<'a>> {
+    if positions.is_empty() {
+        return vec![Span::raw(text)];
+    }
+    let highlight = Style::default().fg(color).add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    let mut current = String::new();
+    let mut current_matched = false;
+    for (i, c) in text.chars().enumerate() {
+        let matched = positions.contains(&i);
+        if matched != current_matched && !current.is_empty() {
+            let style = if current_matched { highlight } else { Style::default() };
+            spans.push(Span::styled(std::mem::take(&mut current), style));
+        }
+        current_matched = matched;
+        current.push(c);
+    }
+    if !current.is_empty() {
+        let style = if current_matched { highlight } else { Style::default() };
+        spans.push(Span::styled(current, style));
+    }
+    spans
 }
 
+/// Widest a key file name may be in the form's key selector.
+const KEY_NAME_MAX: usize = 24;
+
 fn render_form(f: &mut Frame, app: &App, area: Rect) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
         .constraints([
-            Constraint::Length(3),
-            Constraint::Length(3),
-            Constraint::Length(3),
-            Constraint::Length(3),
-            Constraint::Length(3),
-            Constraint::Length(3),
+            Constraint::Min(0),
             Constraint::Length(3),
         ])
         .split(area);
 
-    let form_fields = [
-        ("Name", &app.form_state.name),
-        ("Host", &app.form_state.host),
-        ("Port", &app.form_state.port),
-        ("Username", &app.form_state.username),
-        ("Password", &app.form_state.password),
-        ("Key Passphrase", &app.form_state.key_passphrase),
-    ];
+    let form_fields = app.form_state.fields();
+
+    // Only as many fields as fit are drawn, scrolled to keep the active one in view.
+    let field_height = |i: usize| if i == NOTES_FIELD { 5 } else { 3 };
+    let active = app.form_state.active_field.min(FORM_FIELD_COUNT - 1);
+    let mut first = 0;
+    while first < active && (first..=active).map(field_height).sum::<u16>() > chunks[0].height {
+        first += 1;
+    }
 
-    for (i, (title, content)) in form_fields.iter().enumerate() {
+    let mut y = chunks[0].y;
+    for (i, (title, value)) in form_fields.iter().enumerate().skip(first) {
+        if y >= chunks[0].bottom() {
+            break;
+        }
         let style = if app.form_state.active_field == i {
-            Style::default().fg(Color::Yellow)
+            Style::default().fg(app.theme.highlight)
         } else {
             Style::default()
         };
 
-        let display_content = if (i == 4 || i == 5) && !content.is_empty() {
-            "*".repeat(content.len())
+        let content = match value {
+            FieldValue::Text(text) => *text,
+            FieldValue::Check(_) => "",
+        };
+        let uses_default_passphrase = i == 5
+            && content.is_empty()
+            && app.default_passphrase != PassphraseSource::None
+            && !matches!(app.form_state.selected_key, None | Some(0));
+        let secret = i == 4 || i == 5;
+        let revealed = secret && app.form_state.revealed && app.form_state.active_field == i;
+        let display_content = if let FieldValue::Check(on) = value {
+            check_box(*on).to_string()
+        } else if secret && !revealed && !content.is_empty() {
+            "*".repeat(content.chars().count())
+        } else if uses_default_passphrase {
+            "(using default passphrase source)".to_string()
         } else {
             content.to_string()
         };
 
-        let input = Paragraph::new(display_content)
+        let field_area = Rect {
+            x: chunks[0].x,
+            y,
+            width: chunks[0].width,
+            height: field_height(i),
+        }
+        .intersection(chunks[0]);
+        y += field_height(i);
+        let content_style = if uses_default_passphrase {
+            app.theme.dim()
+        } else {
+            Style::default()
+        };
+        let field_error = match i {
+            0 if app.form_name_taken() => Some("already used by another connection"),
+            2 => app.form_state.port_error().map(|_| "must be 1 to 65535"),
+            _ => None,
+        };
+        let title = if let Some(error) = field_error {
+            format!("{} ({})", title, error)
+        } else if secret && !content.is_empty() && !revealed {
+            format!("{} (hidden — Ctrl+R to reveal)", title)
+        } else {
+            title.to_string()
+        };
+        let title = text::truncate(&title, field_area.width.saturating_sub(2) as usize);
+        let title = if field_error.is_some() {
+            Span::styled(title, Style::default().fg(app.theme.error))
+        } else {
+            Span::raw(title)
+        };
+        let input = Paragraph::new(Text::styled(display_content, content_style))
+            .wrap(Wrap { trim: false })
             .style(style)
-            .block(Block::default().title(*title).borders(Borders::ALL));
-        f.render_widget(input, chunks[i]);
+            .block(app.theme.block().title(title));
+        f.render_widget(input, field_area);
     }
 
     let key_items = {
         let mut items = Vec::new();
         
-        let is_none_selected = match app.form_state.selected_key {
-            Some(0) => true,
-            _ => false
-        };
+        let is_none_selected = matches!(app.form_state.selected_key, Some(0));
         
         let none_display_text = if is_none_selected {
             "《 none 》".to_string()
@@ -498,7 +1786,7 @@ fn render_form(f: &mut Frame, app: &App, area: Rect) {
             none_display_text,
             if is_none_selected {
                 Style::default()
-                    .fg(Color::Yellow)
+                    .fg(app.theme.highlight)
                     .add_modifier(Modifier::BOLD)
             } else {
                 Style::default()
@@ -507,10 +1795,10 @@ fn render_form(f: &mut Frame, app: &App, area: Rect) {
         
         for (i, path) in app.ssh_keys.iter().enumerate() {
             let is_selected = app.form_state.selected_key == Some(i + 1);
-            let file_name = path.file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string();
+            let file_name = text::truncate(
+                &path.file_name().unwrap_or_default().to_string_lossy(),
+                KEY_NAME_MAX,
+            );
 
             let display_text = if is_selected {
                 format!("《 {} 》", file_name)
@@ -522,7 +1810,7 @@ fn render_form(f: &mut Frame, app: &App, area: Rect) {
                 display_text,
                 if is_selected {
                     Style::default()
-                        .fg(Color::Yellow)
+                        .fg(app.theme.highlight)
                         .add_modifier(Modifier::BOLD)
                 } else {
                     Style::default()
@@ -535,18 +1823,23 @@ fn render_form(f: &mut Frame, app: &App, area: Rect) {
 
     let key_text = Line::from(key_items);
     
+    let selected_public = app
+        .form_state
+        .selected_key
+        .and_then(|selected| app.ssh_keys.get(selected.checked_sub(1)?))
+        .and_then(|path| PublicKey::of_key(path));
     let key_paragraph = Paragraph::new(key_text)
         .alignment(Alignment::Center)
-        .block(Block::default()
+        .block(app.theme.block()
             .title("SSH Key (←→ to select)")
-            .borders(Borders::ALL)
+            .title_bottom(Line::from(selected_public.map(|public| public.label()).unwrap_or_default()).centered())
             .style(if app.form_state.active_field == 5 {
-                Style::default().fg(Color::Yellow)
+                Style::default().fg(app.theme.highlight)
             } else {
                 Style::default()
             }));
 
-    f.render_widget(key_paragraph, chunks[6]);
+    f.render_widget(key_paragraph, chunks[1]);
 }
 
 fn render_settings(f: &mut Frame, app: &App, area: Rect) {
@@ -558,12 +1851,90 @@ fn render_settings(f: &mut Frame, app: &App, area: Rect) {
         ])
         .split(area);
 
-    let tabs = vec!["SSH Keys"];
-    let tabs = Tabs::new(tabs)
-        .select(0)
-        .block(Block::default().borders(Borders::ALL).title("Settings"))
-        .highlight_style(Style::default().fg(Color::Yellow));
-    f.render_widget(tabs, chunks[0]);
+    // The tab column is narrow, so tabs are stacked rather than laid out in a row.
+    let selected_tab = match app.settings_tab {
+        SettingsTab::SshKeys => 0,
+        SettingsTab::General => 1,
+    };
+    let tabs = List::new(vec![ListItem::new("SSH Keys"), ListItem::new("General")])
+        .block(app.theme.block().title("Settings"))
+        .highlight_style(Style::default().fg(app.theme.highlight));
+    f.render_stateful_widget(
+        tabs,
+        chunks[0],
+        &mut ListState::default().with_selected(Some(selected_tab)),
+    );
+
+    if app.settings_tab == SettingsTab::General {
+        let items = vec![
+            ListItem::new(format!("Connection timeout: {}s (←→ to change)", app.connect_timeout_secs)),
+            ListItem::new(format!(
+                "Keepalive interval: {}",
+                match app.keepalive_secs {
+                    0 => "off".to_string(),
+                    secs => format!("{}s", secs),
+                }
+            )),
+            ListItem::new(format!(
+                "Default username for new connections: {} (Enter to change)",
+                if app.default_username.is_empty() { "none" } else { &app.default_username }
+            )),
+            ListItem::new(format!("Sort connections: {}", app.sort_order.label())),
+            ListItem::new(format!("Show use count: {}", on_off(app.show_use_count))),
+            ListItem::new(format!("Notify when background work takes at least: {}s", app.notifications.min_secs)),
+            ListItem::new(format!("  Terminal bell: {}", on_off(app.notifications.bell))),
+            ListItem::new(format!("  Desktop notification: {}", on_off(app.notifications.desktop))),
+            ListItem::new(format!("  For connection tests: {}", on_off(app.notifications.tests))),
+            ListItem::new(format!(
+                "Default key passphrase: {}{}",
+                app.default_passphrase,
+                if matches!(app.default_passphrase, PassphraseSource::Keyring(_)) { " (Enter to rename)" } else { "" }
+            )),
+            ListItem::new(format!("Slow links: connection timeout {}s", app.slow_link_profile.connect_timeout_secs)),
+            ListItem::new(format!("  Compression: {}", on_off(app.slow_link_profile.compression))),
+            ListItem::new(format!(
+                "  Keepalive interval: {}",
+                match app.slow_link_profile.keepalive_secs {
+                    0 => "off".to_string(),
+                    secs => format!("{}s", secs),
+                }
+            )),
+            ListItem::new(format!(
+                "Timestamps: {}{}",
+                app.timestamps.label(),
+                if app.timestamps == TimestampStyle::Absolute { format!(" ({})", app.timestamp_format) } else { String::new() }
+            )),
+            ListItem::new(format!("Check clock skew before certificate logins: {}", on_off(app.clock_skew.enabled))),
+            ListItem::new(format!("Vim navigation (j/k): {}", on_off(app.vim_keys))),
+            ListItem::new(format!("Overwrite key files before deleting them: {}", on_off(app.shred_keys))),
+            ListItem::new(format!("Encrypt connections with a master password: {}", on_off(app.vault.is_some()))),
+            ListItem::new(format!("Keep passwords and passphrases in the system keyring: {}", on_off(app.secrets_in_keyring))),
+            ListItem::new(format!("Never save key passphrases: {}", on_off(app.forget_passphrases))),
+            ListItem::new(format!(
+                "Lock the screen when idle for: {}",
+                match app.auto_lock_mins {
+                    0 => "off".to_string(),
+                    mins => format!("{} min", mins),
+                }
+            )),
+            ListItem::new(format!("Use the built-in ssh client instead of ssh: {}", on_off(app.builtin_ssh))),
+            ListItem::new(format!("Delete expired connections ({})", app.expired_connections().len())),
+        ];
+        let title = format!(
+            "ssh client: {}{}",
+            app.ssh_client.label(),
+            if app.ssh_client.sshpass { "" } else { ", no sshpass" }
+        );
+        let list = List::new(items)
+            .block(app.theme.block().title(text::truncate(&title, chunks[1].width.saturating_sub(2) as usize)))
+            .highlight_style(app.theme.selected());
+        f.render_stateful_widget(
+            list,
+            chunks[1],
+            &mut ListState::default().with_selected(Some(app.settings_selected_item)),
+        );
+        return;
+    }
 
     let items = vec![
         ListItem::new("Add SSH Key File"),
@@ -591,29 +1962,44 @@ fn render_settings(f: &mut Frame, app: &App, area: Rect) {
                 false
             };
             
+            let file_name = text::truncate(
+                &path.file_name().unwrap_or_default().to_string_lossy(),
+                KEY_NAME_MAX,
+            );
             let label = if is_current {
-                format!("  {} (current)", 
-                    path.file_name()
-                        .unwrap_or_default()
-                        .to_string_lossy()
-                )
+                format!("  {} (current)", file_name)
             } else {
-                format!("  {}", 
-                    path.file_name()
-                        .unwrap_or_default()
-                        .to_string_lossy()
-                )
+                format!("  {}", file_name)
             };
             
-            ListItem::new(label)
+            let mut spans = vec![Span::raw(label)];
+            if let Some(public) = PublicKey::of_key(path) {
+                spans.push(Span::raw(format!(" ({})", public.label())));
+            }
+            if sibling_certificate(path).is_some() {
+                spans.push(Span::styled(" [cert]", Style::default().fg(app.theme.highlight)));
+            }
+            spans.push(Span::styled(format!("  ({})", app.key_source(path)), app.theme.dim()));
+            ListItem::new(Line::from(spans))
         }));
 
+    if app.key_trash_row().is_some() {
+        let marker = if app.key_trash_collapsed { "▶" } else { "▼" };
+        key_items.push(ListItem::new(format!("{} Recently removed ({})", marker, app.key_trash.len())));
+        if !app.key_trash_collapsed {
+            key_items.extend(app.key_trash.iter().map(|key| {
+                let file_name = text::truncate(&key.path.file_name().unwrap_or_default().to_string_lossy(), KEY_NAME_MAX);
+                ListItem::new(format!("  {}  ({})", file_name, key.source)).style(app.theme.dim())
+            }));
+        }
+    }
+
     let mut all_items = items;
     all_items.append(&mut key_items);
 
     let list = List::new(all_items)
-        .block(Block::default().borders(Borders::ALL))
-        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        .block(app.theme.block())
+        .highlight_style(app.theme.selected());
 
     f.render_stateful_widget(
         list,
@@ -622,37 +2008,437 @@ fn render_settings(f: &mut Frame, app: &App, area: Rect) {
     );
 }
 
+/// A form checkbox; `None` is one left at its default.
+fn check_box(value: Option<bool>) -> &'static str {
+    match value {
+        Some(true) => "[x]",
+        Some(false) => "[ ]",
+        None => "[-]",
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value { "on" } else { "off" }
+}
+
 fn render_file_browser(f: &mut Frame, app: &App, area: Rect) {
     if let Some(browser) = &app.file_browser {
-        let items: Vec<ListItem> = browser
-            .entries
-            .iter()
-            .map(|path| {
-                let name = browser.get_display_name(path);
-                let prefix = if path.is_dir() { "📁 " } else { "📄 " };
-                ListItem::new(format!("{}{}", prefix, name))
-            })
-            .collect();
+        render_browser(f, app, browser, area, true);
+    }
+}
 
-        let title = format!("Browse: {}", browser.current_path.display());
-        let list = List::new(items)
-            .block(Block::default().title(title).borders(Borders::ALL))
-            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
-            .highlight_symbol("> ");
+/// One browser's listing in `area`. The border of the `focused` one is
+/// highlighted in the dual browser; a disconnected one is dimmed.
+fn render_browser(f: &mut Frame, app: &App, browser: &FileBrowser, area: Rect, focused: bool) {
+    // Inside the borders, minus the highlight symbol and the icon.
+    let name_width = area.width.saturating_sub(7) as usize;
+    let items: Vec<ListItem> = browser
+        .entries
+        .iter()
+        .map(|path| {
+            let name = browser.get_display_name(path);
+            let prefix = if browser.is_dir(path) { "📁 " } else { "📄 " };
+            ListItem::new(format!("{}{}", prefix, text::truncate(&name, name_width)))
+        })
+        .collect();
 
-        f.render_stateful_widget(
-            list,
-            area,
-            &mut ListState::default().with_selected(Some(browser.selected)),
+    let mut title = match browser.remote_host() {
+        Some(host) => format!("Browse: {}:{}", host, browser.current_path.display()),
+        None => format!("Browse: {}", browser.current_path.display()),
+    };
+    if browser.show_hidden {
+        title.push_str(" (showing hidden)");
+    }
+    if browser.keys_only {
+        title.push_str(" (key files only, k shows all)");
+    }
+    let mut block = app.theme.block().title(text::truncate(&title, area.width.saturating_sub(2) as usize));
+    if focused && app.input_mode == InputMode::DualBrowser {
+        block = block.border_style(Style::default().fg(app.theme.highlight));
+    }
+    let error = match &browser.error {
+        Some(error) if browser.disconnected => Some(format!("Disconnected: {} — r reconnects", error)),
+        error => error.clone(),
+    };
+    if let Some(error) = error {
+        block = block.title_bottom(
+            Line::from(text::truncate(&error, area.width.saturating_sub(2) as usize)).style(Style::default().fg(app.theme.error)),
         );
     }
+    let mut list = List::new(items).block(block);
+    if browser.disconnected {
+        list = list.style(app.theme.dim());
+    } else if focused {
+        list = list.highlight_style(app.theme.selected()).highlight_symbol("> ");
+    }
+
+    f.render_stateful_widget(
+        list,
+        area,
+        &mut ListState::default().with_selected(Some(browser.selected)),
+    );
+}
+
+/// Local files on the left and remote ones on the right, over a strip
+/// listing the running copy and the ones queued behind it.
+fn render_dual_browser(f: &mut Frame, app: &App, area: Rect) {
+    let Some(dual) = &app.dual_browser else {
+        return;
+    };
+    let queued = dual.running.iter().chain(&dual.queue).count() as u16;
+    let [panes, strip] = Layout::vertical([Constraint::Min(0), Constraint::Length(queued.clamp(1, 4) + 2)]).areas(area);
+    let [left, right] = Layout::horizontal([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]).areas(panes);
+    render_browser(f, app, &dual.local, left, dual.focus == Pane::Local);
+    render_browser(f, app, &dual.remote, right, dual.focus == Pane::Remote);
+
+    let width = strip.width.saturating_sub(2) as usize;
+    let mut lines: Vec<Line> = Vec::new();
+    if let Some(copy) = &dual.running {
+        let progress = match app.transfer.as_ref().map(|transfer| (transfer.percent(), transfer.sent)) {
+            Some((Some(percent), _)) => format!("{}%", percent),
+            Some((None, sent)) => format!("{} bytes", sent),
+            None => "starting".to_string(),
+        };
+        lines.push(Line::from(text::truncate(&format!("{} — {}", copy.describe(), progress), width)));
+    }
+    for copy in &dual.queue {
+        lines.push(Line::from(text::truncate(&format!("{} — queued", copy.describe()), width)).style(app.theme.dim()));
+    }
+    if lines.is_empty() {
+        lines.push(Line::from("F5 copies and F6 moves the selected file to the other side").style(app.theme.dim()));
+    }
+    let title = match dual.queue.len() {
+        0 => "Transfers".to_string(),
+        waiting => format!("Transfers ({} queued)", waiting),
+    };
+    f.render_widget(Paragraph::new(lines).block(app.theme.block().title(title)), strip);
+}
+
+/// The text viewer as a popup over most of `area`, with a status line for
+/// the search under the text.
+fn render_text_viewer(f: &mut Frame, app: &mut App, area: Rect) {
+    let theme = &app.theme;
+    let Some(viewer) = app.text_viewer.as_mut() else {
+        return;
+    };
+    let mut title = viewer.title.clone();
+    if let Some(follow) = &app.follow {
+        if follow.paused {
+            title.push_str(&format!(" [paused, {} new]", follow.held()));
+        }
+        if let Some(path) = follow.output_path() {
+            title.push_str(&format!(" [writing {}]", path.display()));
+        }
+    }
+    let popup = Rect {
+        x: area.x + area.width / 10,
+        y: area.y + 1.min(area.height),
+        width: area.width - area.width / 5,
+        height: area.height.saturating_sub(2),
+    };
+    let block = theme.block()
+        .title(text::truncate(&title, popup.width.saturating_sub(2) as usize))
+        .border_style(Style::default().fg(theme.highlight));
+    let inner = block.inner(popup);
+    f.render_widget(Clear, popup);
+    f.render_widget(block, popup);
+    let [text_area, status_area] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+    viewer.set_viewport(text_area.width, text_area.height);
+
+    let rows = viewer.rows();
+    let matches = viewer.matches();
+    let current = viewer.current_match();
+    let match_style = Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD);
+    let lines: Vec<Line> = rows
+        .iter()
+        .skip(viewer.scroll)
+        .take(text_area.height as usize)
+        .map(|row| {
+            let line = viewer.line(row.line);
+            let mut spans = Vec::new();
+            let mut at = row.start;
+            for hit in matches.iter().filter(|hit| hit.line == row.line && hit.start < row.end && hit.end > row.start) {
+                let (start, end) = (hit.start.max(row.start), hit.end.min(row.end));
+                if start > at {
+                    spans.push(Span::raw(line[at..start].to_string()));
+                }
+                let style = if current.is_some_and(|(_, current)| current == *hit) { theme.selected() } else { match_style };
+                spans.push(Span::styled(line[start..end].to_string(), style));
+                at = end;
+            }
+            if at < row.end {
+                spans.push(Span::raw(line[at..row.end].to_string()));
+            }
+            Line::from(spans)
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), text_area);
+
+    let position = format!(
+        "{}–{} of {}{}",
+        (viewer.scroll + 1).min(rows.len()),
+        (viewer.scroll + text_area.height as usize).min(rows.len()),
+        rows.len(),
+        if viewer.wrap { "" } else { " (wrap off)" }
+    );
+    let search = match (&viewer.search_input, current) {
+        (Some(input), _) => format!("/{}", input),
+        (None, _) if viewer.query().is_empty() => String::new(),
+        (None, Some((index, _))) => format!("\"{}\" {}/{}", viewer.query(), index + 1, matches.len()),
+        (None, None) => format!("\"{}\" not found", viewer.query()),
+    };
+    let ended = app.follow.as_ref().and_then(|follow| follow.ended.as_ref());
+    match ended {
+        Some(reason) if search.is_empty() => {
+            let reason = if reason.is_empty() { "Stream ended" } else { reason.as_str() };
+            let status = format!("{} — r reconnects", reason);
+            f.render_widget(Paragraph::new(Span::styled(status, Style::default().fg(theme.error))), status_area);
+        }
+        _ => f.render_widget(Paragraph::new(Span::styled(search, Style::default().fg(theme.highlight))), status_area),
+    }
+    f.render_widget(Paragraph::new(Span::styled(position, theme.dim())).alignment(Alignment::Right), status_area);
+}
+
+/// Every field of the selected connection in a popup over the list. Secrets
+/// are only said to be there, as 🔒 while the master password is needed.
+fn render_details(f: &mut Frame, app: &App, area: Rect) {
+    let Some(idx) = app.selected_connection.filter(|&idx| idx < app.connections.len()) else {
+        return;
+    };
+    let conn = &app.connections[idx];
+    let none = || "—".to_string();
+    let list = |items: &[String]| if items.is_empty() { none() } else { items.join(", ") };
+
+    let auth = if conn.key_path.as_deref().is_some_and(is_security_key) {
+        "security key, touched when ssh connects"
+    } else if conn.key_path.is_some() {
+        "key file"
+    } else if conn.ask_password && !conn.has_password() {
+        "password, asked on connect"
+    } else if conn.has_password() {
+        "stored password"
+    } else if conn.use_agent {
+        "ssh-agent"
+    } else {
+        "none"
+    };
+    let status = match conn.last_connection_status {
+        _ if app.is_testing(idx) => "testing…",
+        Some(true) => "succeeded",
+        Some(false) => "failed",
+        None => "not tested",
+    };
+    let mut fields = vec![
+        ("Name", conn.name.clone()),
+        ("Host", conn.host.clone()),
+        ("Port", conn.port.to_string()),
+        ("Username", conn.username.clone()),
+        ("Authentication", auth.to_string()),
+        ("Key file", conn.key_path.as_ref().map_or_else(none, |path| path.display().to_string())),
+        ("Certificate", certificate_path(conn).map_or_else(none, |path| path.display().to_string())),
+        ("Tags", list(&conn.tags)),
+        ("Group", conn.group.clone().unwrap_or_else(none)),
+        ("Last connected", conn.last_connected.map_or_else(none, |at| app.format_timestamp(at))),
+        ("Last test", status.to_string()),
+        ("Times connected", conn.use_count.to_string()),
+    ];
+    let secret = |plain: bool, keyring: bool, sealed: bool| match () {
+        _ if sealed && app.secrets_locked() => Some("🔒".to_string()),
+        _ if sealed => Some("saved, encrypted".to_string()),
+        _ if keyring => Some("in the system keyring".to_string()),
+        _ => plain.then(|| "saved".to_string()),
+    };
+    let optional = [
+        ("Password", secret(conn.password.is_some(), conn.password_keyring.is_some(), conn.password_sealed.is_some())),
+        (
+            "Key passphrase",
+            secret(conn.key_passphrase.is_some(), conn.passphrase_keyring.is_some(), conn.passphrase_sealed.is_some()),
+        ),
+        ("Jump host", conn.jump_host.clone()),
+        ("Proxy", conn.proxy.clone()),
+        ("Local forwards", Some(conn.local_forwards.join(", ")).filter(|s| !s.is_empty())),
+        ("Remote forwards", Some(conn.remote_forwards.join(", ")).filter(|s| !s.is_empty())),
+        ("Remote command", conn.remote_command.clone()),
+        ("Extra ssh arguments", conn.extra_args.clone()),
+        ("ssh options", Some(conn.ssh_options.join(", ")).filter(|s| !s.is_empty())),
+        ("Host key checking", conn.host_key_policy.map(|policy| policy.label().to_string())),
+        ("Expires", conn.expires_at.map(|at| app.format_timestamp(at))),
+        ("Keepalive", conn.keepalive_secs.map(|secs| match conn.keepalive_count_max {
+            Some(count) => format!("every {}s, give up after {} missed", secs, count),
+            None => format!("every {}s", secs),
+        })),
+        ("Keepalive count max", conn.keepalive_count_max.filter(|_| conn.keepalive_secs.is_none()).map(|count| count.to_string())),
+        ("Slow link", conn.slow_link.then(|| "yes".to_string())),
+        ("Archived", conn.archived.then(|| "yes".to_string())),
+    ];
+    fields.extend(optional.into_iter().filter_map(|(label, value)| Some((label, value?))));
+
+    let label_width = fields.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines: Vec<Line> = fields
+        .into_iter()
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(format!("{:width$}  ", label, width = label_width), bold),
+                Span::raw(value),
+            ])
+        })
+        .collect();
+    if let Some(notes) = &conn.notes {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("Notes", bold)));
+        lines.extend(notes.lines().map(Line::from));
+    }
+
+    let popup = Rect {
+        x: area.x + area.width / 10,
+        y: area.y + 1.min(area.height),
+        width: area.width - area.width / 5,
+        height: area.height.saturating_sub(2),
+    };
+    let panel = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        app.theme.block()
+            .title(text::truncate(&format!("Details — {}", conn.name), popup.width.saturating_sub(2) as usize))
+            .border_style(Style::default().fg(app.theme.highlight)),
+    );
+    f.render_widget(Clear, popup);
+    f.render_widget(panel, popup);
+}
+
+fn render_prompt(f: &mut Frame, app: &App, area: Rect, kind: PromptKind) {
+    let input = if kind.is_secret() {
+        "*".repeat(app.prompt_input.chars().count())
+    } else {
+        app.prompt_input.to_string()
+    };
+    let dialog_area = Rect {
+        x: area.x + area.width / 4,
+        y: area.y + area.height / 3,
+        width: area.width / 2,
+        height: 3.min(area.height),
+    };
+    let dialog = Paragraph::new(input).block(
+        app.theme.block()
+            .title(text::truncate(kind.title(), dialog_area.width.saturating_sub(2) as usize))
+            .border_style(Style::default().fg(app.theme.highlight)),
+    );
+    f.render_widget(Clear, dialog_area);
+    f.render_widget(dialog, dialog_area);
+}
+
+/// The Ctrl+P palette over the connection list: the query, then the
+/// matching commands with their keys and the matched characters
+/// highlighted.
+fn render_palette(f: &mut Frame, app: &App, area: Rect) {
+    let popup = Rect {
+        x: area.x + area.width / 4,
+        y: area.y + 1.min(area.height),
+        width: area.width / 2,
+        height: area.height.saturating_sub(2).min(16),
+    };
+    let title = format!("Command palette ({})", app.match_mode.label());
+    let block = app.theme.block()
+        .title(text::truncate(&title, popup.width.saturating_sub(2) as usize))
+        .border_style(Style::default().fg(app.theme.highlight));
+    let inner = block.inner(popup);
+    f.render_widget(Clear, popup);
+    f.render_widget(block, popup);
+    let [query_area, list_area] = Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+    f.render_widget(Paragraph::new(format!("> {}", app.palette.query)), query_area);
+
+    let matches = match app.palette.matches(app.match_mode) {
+        Ok(matches) => matches,
+        Err(e) => {
+            f.render_widget(Paragraph::new(e).style(Style::default().fg(app.theme.error)), list_area);
+            return;
+        }
+    };
+    let items: Vec<ListItem> = matches
+        .iter()
+        .map(|(command, found)| {
+            let mut spans = vec![Span::styled(format!("{:>2}  ", command.key), app.theme.dim())];
+            spans.extend(owned(highlight_matches(command.label, &found.positions, app.theme.highlight)));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let mut state = ListState::default();
+    if !matches.is_empty() {
+        state.select(Some(app.palette.selected.min(matches.len() - 1)));
+    }
+    let list = List::new(items).highlight_style(app.theme.selected());
+    f.render_stateful_widget(list, list_area, &mut state);
+}
+
+/// The master password prompt shown in place of everything else while the
+/// connections are locked.
+fn render_unlock(f: &mut Frame, app: &App, area: Rect) {
+    f.render_widget(app.theme.block().title("Connections are encrypted"), area);
+    let dialog_area = Rect {
+        x: area.x + area.width / 4,
+        y: area.y + area.height / 3,
+        width: area.width / 2,
+        height: 3.min(area.height),
+    };
+    let dialog = Paragraph::new("*".repeat(app.prompt_input.chars().count())).block(
+        app.theme.block()
+            .title("Master password")
+            .border_style(Style::default().fg(app.theme.highlight)),
+    );
+    f.render_widget(Clear, dialog_area);
+    f.render_widget(dialog, dialog_area);
+}
+
+/// The idle lock screen; nothing about the connections shows through.
+fn render_locked(f: &mut Frame, app: &App, area: Rect) {
+    f.render_widget(app.theme.block().title("Locked after inactivity"), area);
+    let dialog_area = Rect {
+        x: area.x + area.width / 4,
+        y: area.y + area.height / 3,
+        width: area.width / 2,
+        height: 3.min(area.height),
+    };
+    let (text, title) = match app.vault {
+        Some(_) => ("*".repeat(app.prompt_input.chars().count()), "Master password"),
+        None => ("Press any key to unlock".to_string(), "Locked"),
+    };
+    let dialog = Paragraph::new(text).block(
+        app.theme.block()
+            .title(title)
+            .border_style(Style::default().fg(app.theme.highlight)),
+    );
+    f.render_widget(Clear, dialog_area);
+    f.render_widget(dialog, dialog_area);
 }
 
 fn render_confirmation(f: &mut Frame, app: &App, area: Rect, mode: &ConfirmationMode) {
     let prompt = match mode {
-        ConfirmationMode::Delete => "Are you sure you want to delete this connection?",
-        ConfirmationMode::Duplicate => "Are you sure you want to duplicate this connection?",
-        ConfirmationMode::Update => "Are you sure you want to save these changes?",
+        ConfirmationMode::Delete => "Are you sure you want to delete this connection?".to_string(),
+        ConfirmationMode::Duplicate => "Are you sure you want to duplicate this connection?".to_string(),
+        ConfirmationMode::Update => "Are you sure you want to save these changes?".to_string(),
+        ConfirmationMode::DeleteMarked => format!("Delete {} marked connections?", app.marked.len()),
+        ConfirmationMode::DeleteExpired => {
+            format!("Delete {} expired connections?", app.expired_connections().len())
+        }
+        ConfirmationMode::RemoveKeySource => {
+            let source = app.selected_settings_key()
+                .map(|path| app.key_source(path))
+                .unwrap_or(KeySource::Auto);
+            format!("Remove all {} keys from {}?", app.keys_from_source(&source).len(), source)
+        }
+        ConfirmationMode::RemoveKey => {
+            let name = app.selected_settings_key()
+                .and_then(|path| path.file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            format!("Remove key {}? It can be restored until you quit.", name)
+        }
+        ConfirmationMode::DeleteKeyFile => "Delete this key from disk? This cannot be undone.".to_string(),
+        ConfirmationMode::TrustHostKey { .. } => "Unknown host. Trust its key and add it to known_hosts?".to_string(),
+        ConfirmationMode::Bootstrap { .. } => {
+            "Bootstrap check failed. Run the bootstrap script first? (No connects without it)".to_string()
+        }
+        ConfirmationMode::FixKeyPermissions { .. } => "ssh refuses keys others can read. Make it private (chmod 600)?".to_string(),
+        ConfirmationMode::OverwriteDownload { .. } => "A file with this name exists. Replace it with the download?".to_string(),
     };
 
     let dialog_area = Rect {
@@ -662,13 +2448,72 @@ fn render_confirmation(f: &mut Frame, app: &App, area: Rect, mode: &Confirmation
         height: area.height / 3,
     };
 
-    let dialog = Block::default()
+    let dialog = app.theme.block()
         .title(prompt)
-        .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Yellow));
+        .border_style(Style::default().fg(app.theme.highlight));
     f.render_widget(Clear, dialog_area);
     f.render_widget(dialog, dialog_area);
 
+    // Details under the question, for the dialogs that have any.
+    let body = match mode {
+        ConfirmationMode::TrustHostKey { .. } => app.pending_host_key.as_ref().map(|key| {
+            vec![
+                Line::from(format!("Host: {}", key.pattern())),
+                Line::from(format!("{} key fingerprint:", key.key_type)),
+                Line::from(key.fingerprint.clone()),
+                Line::from("Accept only if it matches the server's real fingerprint."),
+            ]
+        }),
+        ConfirmationMode::OverwriteDownload { .. } => app.pending_download.as_ref().map(|(_, _, target)| {
+            vec![Line::from(target.display().to_string())]
+        }),
+        ConfirmationMode::FixKeyPermissions { .. } => app.pending_key_permissions.as_ref().map(|e| {
+            vec![
+                Line::from(e.path.display().to_string()),
+                Line::from(format!("Permissions: {:04o}", e.mode)),
+            ]
+        }),
+        ConfirmationMode::DeleteKeyFile => app.selected_settings_key().map(|path| {
+            let users = app.connections_using_key(path).len();
+            let mut body = vec![
+                Line::from(path.display().to_string()),
+                Line::from(format!("{}.pub", path.display())),
+            ];
+            if users > 0 {
+                body.push(Line::from(format!("{} connections using it will be left without a key", users)));
+            }
+            body
+        }),
+        ConfirmationMode::Bootstrap { .. } => app.selected_connection
+            .and_then(|idx| app.connections.get(idx))
+            .map(|conn| {
+                vec![
+                    Line::from(format!("Check: {}", conn.bootstrap_check.as_deref().unwrap_or_default())),
+                    Line::from(format!(
+                        "Script: {}",
+                        conn.bootstrap_script.as_ref().map(|path| path.display().to_string()).unwrap_or_default()
+                    )),
+                ]
+            }),
+        ConfirmationMode::DeleteExpired => {
+            let names: Vec<&str> = app.expired_connections()
+                .into_iter()
+                .map(|idx| app.connections[idx].name.as_str())
+                .collect();
+            Some(vec![Line::from(names.join(", "))])
+        }
+        _ => None,
+    };
+    if let Some(body) = body {
+        let body_area = Rect {
+            x: dialog_area.x + 2,
+            y: dialog_area.y + 1,
+            width: dialog_area.width.saturating_sub(4),
+            height: (dialog_area.height * 2 / 3).saturating_sub(3),
+        };
+        f.render_widget(Paragraph::new(body).wrap(Wrap { trim: false }), body_area);
+    }
+
     let centered_button_layout = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
@@ -715,31 +2560,30 @@ fn render_confirmation(f: &mut Frame, app: &App, area: Rect, mode: &Confirmation
     let no_style = if !app.confirmation_selected {
         Style::default()
             .fg(Color::Black)
-            .bg(Color::Green)
+            .bg(app.theme.button)
     } else {
         Style::default()
             .fg(Color::White)
-            .bg(Color::DarkGray)
+            .bg(app.theme.dim)
     };
 
     let yes_style = if app.confirmation_selected {
         Style::default()
             .fg(Color::Black)
-            .bg(Color::Green)
+            .bg(app.theme.button)
     } else {
         Style::default()
             .fg(Color::White)
-            .bg(Color::DarkGray)
+            .bg(app.theme.dim)
     };
 
     let no_button = Paragraph::new(" No ")
         .alignment(Alignment::Center)
         .style(no_style)
-        .block(Block::default()
-            .borders(Borders::ALL)
+        .block(app.theme.block()
             .border_style(
                 if !app.confirmation_selected {
-                    Style::default().fg(Color::Green)
+                    Style::default().fg(app.theme.button)
                 } else {
                     Style::default()
                 }
@@ -748,11 +2592,10 @@ fn render_confirmation(f: &mut Frame, app: &App, area: Rect, mode: &Confirmation
     let yes_button = Paragraph::new(" Yes ")
         .alignment(Alignment::Center)
         .style(yes_style)
-        .block(Block::default()
-            .borders(Borders::ALL)
+        .block(app.theme.block()
             .border_style(
                 if app.confirmation_selected {
-                    Style::default().fg(Color::Green)
+                    Style::default().fg(app.theme.button)
                 } else {
                     Style::default()
                 }
@@ -760,4 +2603,279 @@ fn render_confirmation(f: &mut Frame, app: &App, area: Rect, mode: &Confirmation
     
     f.render_widget(no_button, no_button_area);
     f.render_widget(yes_button, yes_button_area);
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use peroxide::SshConnection;
+    use peroxide::dual_browser::DualBrowser;
+    use ratatui::backend::TestBackend;
+
+    fn test_app(names: &[&str]) -> App {
+        let mut app = App::new();
+        app.persist = false;
+        app.connections = names
+            .iter()
+            .map(|name| SshConnection {
+                name: name.to_string(),
+                host: "example.com".to_string(),
+                username: "deploy".to_string(),
+                port: 22,
+                ..SshConnection::default()
+            })
+            .collect();
+        app
+    }
+
+    /// Renders `draw` on a `width` by `height` test terminal.
+    fn render(width: u16, height: u16, draw: impl FnOnce(&mut Frame)) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(draw).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    /// Row `y` of `buffer` as text, skipping the cells hidden behind
+    /// wide characters.
+    fn row_text(buffer: &Buffer, y: u16) -> String {
+        (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect()
+    }
+
+    /// The column of the first cell on row `y` whose symbol is `symbol`.
+    fn column_of(buffer: &Buffer, y: u16, symbol: &str) -> Option<u16> {
+        (0..buffer.area.width).find(|&x| buffer[(x, y)].symbol() == symbol)
+    }
+
+    #[test]
+    fn connection_columns_line_up_with_wide_names() {
+        let mut app = test_app(&["web", "東京サーバ", "🚀 deploy", "e\u{301}cole"]);
+        let buffer = render(80, 8, |f| {
+            let area = f.area();
+            render_connections(f, &mut app, area);
+        });
+
+        let columns: Vec<Option<u16>> = (1..5).map(|y| column_of(&buffer, y, "(")).collect();
+        assert!(columns[0].is_some(), "{:?}", buffer);
+        assert!(columns.iter().all(|&column| column == columns[0]), "{:?}\n{:?}", columns, buffer);
+    }
+
+    #[test]
+    fn long_wide_names_are_cut_to_the_name_column() {
+        let long = "東京".repeat(30);
+        let mut app = test_app(&[&long, "web"]);
+        let buffer = render(80, 6, |f| {
+            let area = f.area();
+            render_connections(f, &mut app, area);
+        });
+
+        assert_eq!(column_of(&buffer, 1, "("), column_of(&buffer, 2, "("));
+        assert!((0..80).any(|x| buffer[(x, 1)].symbol() == "…"), "{:?}", buffer);
+    }
+
+    #[test]
+    fn title_and_help_bar_show_the_same_mode() {
+        let mut app = test_app(&["web"]);
+        app.input_mode = InputMode::Settings;
+        let description = mode_description(&app);
+        let buffer = render(140, 40, |f| ui(f, &mut app));
+
+        let rows: Vec<String> = (0..40).map(|y| row_text(&buffer, y)).collect();
+        let showing: Vec<&String> = rows.iter().filter(|row| row.contains(&description)).collect();
+        assert!(showing.len() >= 2, "{} is not in both bars:\n{}", description, rows.join("\n"));
+        let title = format!("Peroxide - SSH Connection Manager — {}", description);
+        assert!(rows.iter().any(|row| row.contains(&title)), "{}", rows.join("\n"));
+    }
+
+    /// Runs "test all" to the end, drawing the list after every update
+    /// the way the event loop does.
+    fn test_all_to_the_end(app: &mut App) -> Vec<Buffer> {
+        app.start_test_all().unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        let mut frames = Vec::new();
+        while app.test_run.is_some() {
+            assert!(std::time::Instant::now() < deadline, "the simulated test run never finished");
+            app.poll_test_run();
+            frames.push(render(100, 12, |f| {
+                let area = f.area();
+                render_connections(f, app, area);
+            }));
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        frames
+    }
+
+    fn status_row(buffer: &Buffer, name: &str) -> String {
+        (0..buffer.area.height)
+            .map(|y| row_text(buffer, y))
+            .find(|row| row.contains(name))
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn simulated_test_all_drives_the_status_icons() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios/flaky.json");
+        let mut app = App::new();
+        start_simulation(&mut app, Scenario::load(&fixture).unwrap());
+        assert!(!app.persist);
+        assert_eq!(app.connections.len(), 3);
+
+        let frames = test_all_to_the_end(&mut app);
+        assert!(frames.iter().any(|frame| status_row(frame, "flaky-2").contains('⏳')), "no spinner was drawn");
+        let last = frames.last().unwrap();
+        for name in ["flaky-1", "flaky-2", "steady"] {
+            assert!(status_row(last, name).contains('✅'), "{}", status_row(last, name));
+        }
+        assert_eq!(app.error_message.as_deref().map(|m| m.ends_with("3 ok, 0 failed")), Some(true));
+
+        // flaky-1 fails every second attempt.
+        let last = test_all_to_the_end(&mut app).pop().unwrap();
+        assert!(status_row(&last, "flaky-1").contains('❌'), "{}", status_row(&last, "flaky-1"));
+        assert!(status_row(&last, "steady").contains('✅'));
+        assert_eq!(app.connections[0].last_connection_status, Some(false));
+    }
+
+    /// Renders the viewer popup on a 40 by 12 terminal. The text area is
+    /// then rows 2..9 from column 5.
+    fn render_viewer(app: &mut App) -> Buffer {
+        render(40, 12, |f| render_text_viewer(f, app, f.area()))
+    }
+
+    fn viewer_keys(app: &mut App, keys: &str) {
+        let viewer = app.text_viewer.as_mut().unwrap();
+        for c in keys.chars() {
+            let code = if c == '\n' { KeyCode::Enter } else { KeyCode::Char(c) };
+            viewer.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+    }
+
+    /// Whether `cell` carries everything `style` sets.
+    fn styled(cell: &ratatui::buffer::Cell, style: Style) -> bool {
+        style.fg.is_none_or(|fg| cell.fg == fg)
+            && style.bg.is_none_or(|bg| cell.bg == bg)
+            && cell.modifier.contains(style.add_modifier)
+    }
+
+    #[test]
+    fn text_viewer_scrolls_by_line_page_and_end() {
+        let mut app = test_app(&[]);
+        let content: Vec<String> = (1..=20).map(|i| format!("line {:02}", i)).collect();
+        app.text_viewer = Some(TextViewer::new("Log", &content.join("\n")));
+        let first_row = |buffer: &Buffer| row_text(buffer, 2).trim().trim_matches('│').trim().to_string();
+
+        assert_eq!(first_row(&render_viewer(&mut app)), "line 01");
+        viewer_keys(&mut app, "jjj");
+        assert_eq!(first_row(&render_viewer(&mut app)), "line 04");
+        viewer_keys(&mut app, " ");
+        assert_eq!(first_row(&render_viewer(&mut app)), "line 10");
+        viewer_keys(&mut app, "G");
+        let buffer = render_viewer(&mut app);
+        assert_eq!(first_row(&buffer), "line 14");
+        assert!(row_text(&buffer, 8).contains("line 20"));
+        assert!(row_text(&buffer, 9).contains("14–20 of 20"), "{}", row_text(&buffer, 9));
+        // Scrolling past the end stays on the last page.
+        viewer_keys(&mut app, "jj");
+        assert_eq!(first_row(&render_viewer(&mut app)), "line 14");
+        app.text_viewer.as_mut().unwrap().handle_key(KeyEvent::new(KeyCode::PageUp, KeyModifiers::NONE));
+        assert_eq!(first_row(&render_viewer(&mut app)), "line 08");
+        viewer_keys(&mut app, "g");
+        assert_eq!(first_row(&render_viewer(&mut app)), "line 01");
+    }
+
+    #[test]
+    fn text_viewer_highlights_search_hits_and_the_current_one() {
+        let mut app = test_app(&[]);
+        let mut content: Vec<String> = (1..=12).map(|i| format!("filler {}", i)).collect();
+        content.extend(["beta web".to_string(), "web and Web".to_string()]);
+        app.text_viewer = Some(TextViewer::new("Output", &content.join("\n")));
+        render_viewer(&mut app);
+        viewer_keys(&mut app, "/web\n");
+
+        let selected = app.theme.selected();
+        let hit = Style::default().fg(app.theme.highlight).add_modifier(Modifier::BOLD);
+        let buffer = render_viewer(&mut app);
+        // The first hit is on line 13; the viewer scrolled just far enough
+        // to show it on the last text row.
+        assert!(row_text(&buffer, 8).contains("beta web"), "{}", row_text(&buffer, 8));
+        assert!(row_text(&buffer, 9).contains("\"web\" 1/3"), "{}", row_text(&buffer, 9));
+        let x = column_of(&buffer, 8, "w").unwrap();
+        assert!(styled(&buffer[(x, 8)], selected));
+        assert!(!styled(&buffer[(x - 1, 8)], hit));
+
+        viewer_keys(&mut app, "n");
+        let buffer = render_viewer(&mut app);
+        let y = (2..9).find(|&y| row_text(&buffer, y).contains("web and Web")).unwrap();
+        let x = column_of(&buffer, y, "w").unwrap();
+        assert!(styled(&buffer[(x, y)], selected));
+        // Smart case: a lowercase query matches "Web" too, as a plain hit.
+        let upper = column_of(&buffer, y, "W").unwrap();
+        assert!(styled(&buffer[(upper, y)], hit));
+        assert!(row_text(&buffer, 9).contains("\"web\" 2/3"));
+
+        viewer_keys(&mut app, "/Web\n");
+        let buffer = render_viewer(&mut app);
+        assert!(row_text(&buffer, 9).contains("\"Web\" 1/1"), "{}", row_text(&buffer, 9));
+        assert!(!styled(&buffer[(x, y)], hit));
+    }
+
+    #[test]
+    fn form_checkboxes_render_as_boxes() {
+        let mut app = test_app(&[]);
+        app.start_adding();
+        app.form_state.use_agent = true;
+        app.form_state.active_field = 9;
+        let buffer = render(80, 40, |f| render_form(f, &app, f.area()));
+        let row_after = |title: &str| {
+            let y = (0..buffer.area.height).find(|&y| row_text(&buffer, y).contains(title)).unwrap();
+            row_text(&buffer, y + 1)
+        };
+        assert!(row_after("Use SSH Agent").contains("[x]"), "{}", row_after("Use SSH Agent"));
+        assert!(row_after("Ask Password on Connect").contains("[ ]"));
+        assert!(!row_after("Ask Password on Connect").contains("yes"));
+    }
+    #[test]
+    fn palette_lists_matching_commands_with_their_keys() {
+        let mut app = test_app(&["web"]);
+        app.open_palette();
+        "upl".chars().for_each(|c| app.palette.push(c));
+        app.palette.push('z');
+        app.palette.pop();
+        let buffer = render(80, 20, |f| render_palette(f, &app, f.area()));
+        assert!((0..buffer.area.height).any(|y| row_text(&buffer, y).contains("> upl")));
+        let y = (0..buffer.area.height).find(|&y| row_text(&buffer, y).contains("Upload a file")).unwrap();
+        assert!(row_text(&buffer, y).contains(" U  Upload a file"), "{}", row_text(&buffer, y));
+        let x = column_of(&buffer, y, "U").unwrap() + 3;
+        assert_eq!(buffer[(x, y)].symbol(), "U");
+        assert_eq!(buffer[(x, y)].fg, app.theme.highlight);
+        assert_ne!(buffer[(x + 3, y)].fg, app.theme.highlight, "only the query's characters are highlighted");
+    }
+
+    #[test]
+    fn dual_browser_shows_both_sides_the_queue_and_a_lost_remote() {
+        let dir = std::env::temp_dir().join(format!("peroxide-dual-render-{}", std::process::id()));
+        let (left, right) = (dir.join("left"), dir.join("right"));
+        std::fs::create_dir_all(&left).unwrap();
+        std::fs::create_dir_all(&right).unwrap();
+        std::fs::write(left.join("notes.txt"), b"notes").unwrap();
+        let mut app = test_app(&["web"]);
+        app.input_mode = InputMode::DualBrowser;
+        let mut dual = DualBrowser::new(0, FileBrowser::new(left.clone()), FileBrowser::new(right.clone()));
+        dual.local.selected = dual.local.entries.iter().position(|path| path.ends_with("notes.txt")).unwrap();
+        dual.queue_selected(false).unwrap();
+        app.dual_browser = Some(dual);
+
+        let buffer = render(100, 16, |f| render_dual_browser(f, &app, f.area()));
+        let text: Vec<String> = (0..buffer.area.height).map(|y| row_text(&buffer, y)).collect();
+        assert!(text[0].contains(&format!("Browse: {}", left.display())), "{}", text[0]);
+        assert!(text[0].contains(&format!("Browse: {}", right.display())), "{}", text[0]);
+        assert_eq!(buffer[(0, 0)].fg, app.theme.highlight, "the focused pane is outlined");
+        assert_ne!(buffer[(50, 0)].fg, app.theme.highlight);
+        assert!(text.iter().any(|row| row.contains("Transfers (1 queued)")));
+        assert!(text.iter().any(|row| row.contains("↑ copy notes.txt → ") && row.contains("queued")));
+
+        let dual = app.dual_browser.as_mut().unwrap();
+        dual.remote.disconnected = true;
+        dual.remote.error = Some("socket disconnected".to_string());
+        let buffer = render(100, 16, |f| render_dual_browser(f, &app, f.area()));
+        assert!((0..buffer.area.height).any(|y| row_text(&buffer, y).contains("Disconnected: socket disconnected — r reconnects")));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+use regex::{Regex, RegexBuilder};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchMode {
+    #[default]
+    Substring,
+    Fuzzy,
+    Regex,
+}
+
+impl MatchMode {
+    pub fn next(self) -> Self {
+        match self {
+            MatchMode::Substring => MatchMode::Fuzzy,
+            MatchMode::Fuzzy => MatchMode::Regex,
+            MatchMode::Regex => MatchMode::Substring,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MatchMode::Substring => "substring",
+            MatchMode::Fuzzy => "fuzzy",
+            MatchMode::Regex => "regex",
+        }
+    }
+}
+
+/// A successful match: higher scores rank first, `positions` are the char
+/// indices of the haystack that matched, in ascending order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+/// Case-insensitive matcher shared by every list filter so they all behave
+/// the same way for a given query and mode.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    mode: MatchMode,
+    query: Vec<char>,
+    regex: Option<Regex>,
+}
+
+impl Matcher {
+    pub fn new(mode: MatchMode, query: &str) -> Result<Self, String> {
+        let regex = match mode {
+            MatchMode::Regex if !query.is_empty() => Some(
+                RegexBuilder::new(query)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| format!("Invalid regex: {}", e))?,
+            ),
+            _ => None,
+        };
+        Ok(Self {
+            mode,
+            query: query.chars().map(fold).collect(),
+            regex,
+        })
+    }
+
+    pub fn mode(&self) -> MatchMode {
+        self.mode
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.query.is_empty()
+    }
+
+    /// Matches `haystack` against the query. An empty query matches
+    /// everything with no highlighted positions.
+    pub fn matches(&self, haystack: &str) -> Option<Match> {
+        if self.query.is_empty() {
+            return Some(Match { score: 0, positions: Vec::new() });
+        }
+        let chars: Vec<char> = haystack.chars().map(fold).collect();
+        match self.mode {
+            MatchMode::Substring => self.substring(&chars),
+            MatchMode::Fuzzy => self.fuzzy(&chars),
+            MatchMode::Regex => self.regex(haystack),
+        }
+    }
+
+    fn substring(&self, chars: &[char]) -> Option<Match> {
+        let start = chars
+            .windows(self.query.len())
+            .position(|window| window == self.query.as_slice())?;
+        let score = if start == 0 { 100 } else { 50 - start.min(49) as i64 };
+        Some(Match {
+            score,
+            positions: (start..start + self.query.len()).collect(),
+        })
+    }
+
+    fn fuzzy(&self, chars: &[char]) -> Option<Match> {
+        let mut positions: Vec<usize> = Vec::with_capacity(self.query.len());
+        let mut score = 0i64;
+        let mut next = 0;
+        for &wanted in &self.query {
+            let found = next + chars[next..].iter().position(|&c| c == wanted)?;
+            score += 10;
+            if found == 0 || !chars[found - 1].is_alphanumeric() {
+                score += 8;
+            }
+            match positions.last() {
+                Some(&prev) if prev + 1 == found => score += 15,
+                Some(&prev) => score -= (found - prev - 1).min(10) as i64,
+                None => score -= found.min(10) as i64,
+            }
+            positions.push(found);
+            next = found + 1;
+        }
+        Some(Match { score, positions })
+    }
+
+    fn regex(&self, haystack: &str) -> Option<Match> {
+        let found = self.regex.as_ref()?.find(haystack)?;
+        let start = haystack[..found.start()].chars().count();
+        let len = found.as_str().chars().count();
+        Some(Match {
+            score: 50 - start.min(49) as i64,
+            positions: (start..start + len).collect(),
+        })
+    }
+}
+
+/// Folds a character for case-insensitive comparison while keeping a
+/// one-to-one mapping between haystack chars and match positions.
+fn fold(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(mode: MatchMode, query: &str, haystack: &str) -> Option<i64> {
+        Matcher::new(mode, query).unwrap().matches(haystack).map(|m| m.score)
+    }
+
+    fn positions(mode: MatchMode, query: &str, haystack: &str) -> Vec<usize> {
+        Matcher::new(mode, query).unwrap().matches(haystack).unwrap().positions
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        for mode in [MatchMode::Substring, MatchMode::Fuzzy, MatchMode::Regex] {
+            let matcher = Matcher::new(mode, "").unwrap();
+            assert!(matcher.is_empty());
+            assert_eq!(matcher.matches("anything"), Some(Match { score: 0, positions: Vec::new() }));
+        }
+    }
+
+    #[test]
+    fn substring_is_case_insensitive_and_prefers_prefixes() {
+        assert_eq!(positions(MatchMode::Substring, "WEB", "prod-web-01"), vec![5, 6, 7]);
+        assert!(score(MatchMode::Substring, "web", "web-01") > score(MatchMode::Substring, "web", "prod-web"));
+        assert!(score(MatchMode::Substring, "web", "a-web") > score(MatchMode::Substring, "web", "abcdef-web"));
+        assert_eq!(score(MatchMode::Substring, "wbe", "web"), None);
+    }
+
+    #[test]
+    fn fuzzy_ranks_contiguous_and_word_start_matches_higher() {
+        let contiguous = score(MatchMode::Fuzzy, "db", "db-primary").unwrap();
+        let word_starts = score(MatchMode::Fuzzy, "db", "data-backup").unwrap();
+        let scattered = score(MatchMode::Fuzzy, "db", "addressbook").unwrap();
+        assert!(contiguous > word_starts, "{} vs {}", contiguous, word_starts);
+        assert!(word_starts > scattered, "{} vs {}", word_starts, scattered);
+        assert_eq!(positions(MatchMode::Fuzzy, "dbk", "data-backup"), vec![0, 5, 8]);
+        assert_eq!(score(MatchMode::Fuzzy, "bd", "db"), None);
+    }
+
+    #[test]
+    fn ranking_is_stable_for_equal_scores() {
+        let matcher = Matcher::new(MatchMode::Fuzzy, "app").unwrap();
+        let names = ["app-3", "app-1", "app-2", "zapp"];
+        let mut ranked: Vec<(&str, i64)> = names
+            .iter()
+            .filter_map(|name| matcher.matches(name).map(|m| (*name, m.score)))
+            .collect();
+        ranked.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        let order: Vec<&str> = ranked.iter().map(|&(name, _)| name).collect();
+        assert_eq!(order, vec!["app-3", "app-1", "app-2", "zapp"]);
+        // The same query gives the same scores every time.
+        assert_eq!(matcher.matches("app-1"), matcher.matches("app-1"));
+    }
+
+    #[test]
+    fn positions_are_char_indices_for_unicode() {
+        assert_eq!(positions(MatchMode::Substring, "é-f", "café-fés"), vec![3, 4, 5]);
+        assert_eq!(positions(MatchMode::Substring, "ÉCOLE", "l'école"), vec![2, 3, 4, 5, 6]);
+        assert_eq!(positions(MatchMode::Fuzzy, "東京", "東-京都"), vec![0, 2]);
+        assert_eq!(positions(MatchMode::Regex, "京.", "東京都"), vec![1, 2]);
+        // A character whose lowercase is longer still lines up one to one.
+        assert_eq!(positions(MatchMode::Substring, "x", "İx"), vec![1]);
+    }
+
+    #[test]
+    fn invalid_regex_is_reported() {
+        let err = Matcher::new(MatchMode::Regex, "web(").unwrap_err();
+        assert!(err.starts_with("Invalid regex"), "{}", err);
+        assert_eq!(positions(MatchMode::Regex, "^WEB-\\d", "web-01"), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn modes_cycle() {
+        assert_eq!(MatchMode::default().next().next().next(), MatchMode::Substring);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::matcher::{Match, MatchMode, Matcher};
+
+/// An action the command palette offers, run by pressing `key` in the
+/// connection list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Command {
+    pub label: &'static str,
+    pub key: char,
+}
+
+const fn command(label: &'static str, key: char) -> Command {
+    Command { label, key }
+}
+
+/// Every command, in the order shown before anything is typed.
+pub const COMMANDS: &[Command] = &[
+    command("Connect", 'c'),
+    command("Connect with a shell", 'C'),
+    command("Quick connect", 'g'),
+    command("Test connection", 't'),
+    command("Test all connections", 'T'),
+    command("Add connection", 'a'),
+    command("Edit connection", 'e'),
+    command("Duplicate connection", 'y'),
+    command("Delete connection", 'd'),
+    command("Undo", 'u'),
+    command("Show details", 'i'),
+    command("Show host key", 'v'),
+    command("Copy ssh command", 'p'),
+    command("Browse remote files", 'b'),
+    command("Browse local and remote files side by side", 'B'),
+    command("Run a command", 'x'),
+    command("Upload a file", 'U'),
+    command("Download a file", 'D'),
+    command("Follow a remote file", 'F'),
+    command("Archive or unarchive", 'h'),
+    command("Show or hide archived connections", 'H'),
+    command("Search connections", '/'),
+    command("Cycle tag filter", '#'),
+    command("Tag marked connections", '+'),
+    command("Cycle sort order", 'o'),
+    command("Back up connections", 'E'),
+    command("Import connections", 'I'),
+    command("Encrypted export", 'X'),
+    command("Add a key file", 'k'),
+    command("Add a key folder", 'f'),
+    command("Settings", 's'),
+    command("Toggle privacy mode", 'P'),
+    command("Show messages", 'm'),
+    command("Quit", 'q'),
+];
+
+/// The Ctrl+P command palette: a query and the selected row among the
+/// commands it matches. Matching uses the `/` filter's matcher and mode,
+/// so both find the same things for the same query.
+#[derive(Debug, Clone, Default)]
+pub struct Palette {
+    pub query: String,
+    pub selected: usize,
+}
+
+impl Palette {
+    /// Commands matching the query, best first; equal scores keep the
+    /// order of `COMMANDS`. Fails on an invalid regex.
+    pub fn matches(&self, mode: MatchMode) -> Result<Vec<(Command, Match)>, String> {
+        let matcher = Matcher::new(mode, &self.query)?;
+        let mut matches: Vec<(Command, Match)> = COMMANDS
+            .iter()
+            .filter_map(|&command| matcher.matches(command.label).map(|found| (command, found)))
+            .collect();
+        matches.sort_by_key(|(_, found)| std::cmp::Reverse(found.score));
+        Ok(matches)
+    }
+
+    /// The command Enter runs.
+    pub fn chosen(&self, mode: MatchMode) -> Option<Command> {
+        let matches = self.matches(mode).ok()?;
+        matches.get(self.selected.min(matches.len().saturating_sub(1))).map(|&(command, _)| command)
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    /// Moves the selection by `delta` rows among `count` matches, wrapping
+    /// around at the ends.
+    pub fn select(&mut self, delta: isize, count: usize) {
+        if count == 0 {
+            self.selected = 0;
+            return;
+        }
+        let current = self.selected.min(count - 1) as isize;
+        self.selected = (current + delta).rem_euclid(count as isize) as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette(query: &str) -> Palette {
+        Palette { query: query.to_string(), selected: 0 }
+    }
+
+    fn labels(query: &str, mode: MatchMode) -> Vec<&'static str> {
+        palette(query).matches(mode).unwrap().into_iter().map(|(command, _)| command.label).collect()
+    }
+
+    #[test]
+    fn an_empty_query_lists_every_command_in_order() {
+        assert_eq!(labels("", MatchMode::Substring), COMMANDS.iter().map(|c| c.label).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn matching_follows_the_filter_mode() {
+        assert_eq!(labels("upload", MatchMode::Substring), ["Upload a file"]);
+        assert_eq!(labels("dup", MatchMode::Substring), ["Duplicate connection"]);
+        assert_eq!(labels("tstall", MatchMode::Substring), Vec::<&str>::new());
+        assert_eq!(labels("tstall", MatchMode::Fuzzy), ["Test all connections"]);
+        assert_eq!(labels("^(add|edit) ", MatchMode::Regex), ["Add connection", "Edit connection", "Add a key file", "Add a key folder"]);
+        assert!(palette("(").matches(MatchMode::Regex).is_err());
+    }
+
+    #[test]
+    fn prefixes_rank_first() {
+        let found = labels("connect", MatchMode::Substring);
+        assert_eq!(&found[..2], ["Connect", "Connect with a shell"]);
+        assert!(found.contains(&"Quick connect"));
+    }
+
+    #[test]
+    fn selection_wraps_and_typing_resets_it() {
+        let mut palette = palette("connect");
+        let count = palette.matches(MatchMode::Substring).unwrap().len();
+        palette.select(-1, count);
+        assert_eq!(palette.selected, count - 1);
+        palette.select(1, count);
+        assert_eq!(palette.chosen(MatchMode::Substring).map(|c| c.key), Some('c'));
+        palette.select(1, count);
+        assert_eq!(palette.chosen(MatchMode::Substring).map(|c| c.key), Some('C'));
+        palette.push(' ');
+        assert_eq!(palette.selected, 0);
+        assert_eq!(super::Palette { query: "zzz".to_string(), selected: 0 }.chosen(MatchMode::Fuzzy), None);
+    }
+
+    #[test]
+    fn every_key_is_offered_once() {
+        let mut keys: Vec<char> = COMMANDS.iter().map(|c| c.key).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), COMMANDS.len());
+    }
+}
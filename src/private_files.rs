@@ -0,0 +1,180 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Owner read/write only, for files that may hold passwords.
+#[cfg(unix)]
+const FILE_MODE: u32 = 0o600;
+/// Owner only, for the config directory.
+#[cfg(unix)]
+const DIR_MODE: u32 = 0o700;
+
+/// Creates `dir` and its parents. On Unix the directory itself is made
+/// private to the user, even when it already existed.
+pub fn create_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir, fs::Permissions::from_mode(DIR_MODE))?;
+    }
+    Ok(())
+}
+
+/// Writes `content` to `path`, which on Unix only the user can read, from
+/// the moment the file is created.
+pub fn write(path: &Path, content: &str) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(FILE_MODE);
+        // The mode only applies to new files.
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(FILE_MODE))?;
+        }
+    }
+    io::Write::write_all(&mut options.open(path)?, content.as_bytes())
+}
+
+/// A private key that group or others can access, which ssh refuses to
+/// use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPermissionError {
+    pub path: PathBuf,
+    pub mode: u32,
+}
+
+impl fmt::Display for KeyPermissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Key {} has permissions {:04o}; run chmod 600 {}",
+            self.path.display(),
+            self.mode,
+            self.path.display()
+        )
+    }
+}
+
+/// Fails when group or others have any access to the key at `path`. Keys
+/// that cannot be read are left for authentication to report. Always
+/// `Ok` off Unix.
+pub fn check_key_permissions(path: &Path) -> Result<(), KeyPermissionError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(metadata) = fs::metadata(path) {
+            let mode = metadata.permissions().mode() & 0o777;
+            if mode & 0o077 != 0 {
+                return Err(KeyPermissionError { path: path.to_path_buf(), mode });
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Makes `path` private to the user if group or others could read or
+/// write it. Returns whether it was too open. Always `false` off Unix,
+/// and for files that do not exist.
+pub fn tighten(path: &Path) -> io::Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let Ok(metadata) = fs::metadata(path) else {
+            return Ok(false);
+        };
+        let mode = if metadata.is_dir() { DIR_MODE } else { FILE_MODE };
+        if metadata.permissions().mode() & 0o077 != 0 {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+            return Ok(true);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(false)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("peroxide-private-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    fn chmod(path: &Path, mode: u32) {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn new_files_and_dirs_are_private() {
+        let dir = scratch_dir("new").join("peroxide");
+        create_dir(&dir).unwrap();
+        assert_eq!(mode(&dir), 0o700);
+        let file = dir.join("connections.json");
+        write(&file, "[]").unwrap();
+        assert_eq!(mode(&file), 0o600);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "[]");
+    }
+
+    #[test]
+    fn existing_files_and_dirs_are_made_private() {
+        let dir = scratch_dir("existing");
+        chmod(&dir, 0o755);
+        create_dir(&dir).unwrap();
+        assert_eq!(mode(&dir), 0o700);
+        let file = dir.join("connections.json");
+        fs::write(&file, "old content that is longer").unwrap();
+        chmod(&file, 0o644);
+        write(&file, "[]").unwrap();
+        assert_eq!(mode(&file), 0o600);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "[]");
+    }
+
+    #[test]
+    fn tighten_reports_only_what_it_changed() {
+        let dir = scratch_dir("tighten");
+        let file = dir.join("additional_keys.json");
+        assert!(!tighten(&file).unwrap(), "a missing file is left alone");
+        fs::write(&file, "[]").unwrap();
+        chmod(&file, 0o640);
+        assert!(tighten(&file).unwrap());
+        assert_eq!(mode(&file), 0o600);
+        assert!(!tighten(&file).unwrap());
+        chmod(&dir, 0o711);
+        assert!(tighten(&dir).unwrap());
+        assert_eq!(mode(&dir), 0o700);
+    }
+
+    #[test]
+    fn keys_open_to_others_are_refused() {
+        let dir = scratch_dir("keys");
+        let key = dir.join("id_rsa");
+        fs::write(&key, "key").unwrap();
+        for private in [0o600, 0o400] {
+            chmod(&key, private);
+            assert_eq!(check_key_permissions(&key), Ok(()));
+        }
+        for open in [0o644, 0o640, 0o604] {
+            chmod(&key, open);
+            assert_eq!(check_key_permissions(&key), Err(KeyPermissionError { path: key.clone(), mode: open }));
+        }
+        let error = check_key_permissions(&key).unwrap_err();
+        assert_eq!(error.to_string(), format!("Key {0} has permissions 0604; run chmod 600 {0}", key.display()));
+        assert_eq!(check_key_permissions(&dir.join("missing")), Ok(()));
+    }
+}
+
@@ -0,0 +1,170 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{IpAddr, TcpStream};
+use std::time::Duration;
+use crate::AppError;
+
+const SOCKS_VERSION: u8 = 5;
+const DEFAULT_PORT: u16 = 1080;
+
+/// A SOCKS5 proxy for peroxide's own connections (tests, file browsing).
+/// The `ssh` it launches is left alone; use a `ProxyCommand` for that.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socks5Proxy {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Socks5Proxy {
+    /// Parses `[socks5://][user[:password]@]host[:port]`; the port defaults
+    /// to 1080. `socks5h://` is accepted too, since target names are always
+    /// resolved by the proxy. Other schemes yield `None`.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let spec = spec.trim();
+        let rest = match spec.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("socks5") || scheme.eq_ignore_ascii_case("socks5h") => rest,
+            Some(_) => return None,
+            None => spec,
+        };
+        let rest = rest.trim_end_matches('/');
+        let (credentials, address) = match rest.rsplit_once('@') {
+            Some((credentials, address)) => (Some(credentials), address),
+            None => (None, rest),
+        };
+        let (username, password) = match credentials.map(|c| c.split_once(':').unwrap_or((c, ""))) {
+            Some((user, pass)) if !user.is_empty() => {
+                (Some(user.to_string()), Some(pass.to_string()).filter(|p| !p.is_empty()))
+            }
+            Some(_) => return None,
+            None => (None, None),
+        };
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) if !host.ends_with(':') => (host, port.parse().ok()?),
+            _ => (address, DEFAULT_PORT),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() || host.contains(char::is_whitespace) {
+            return None;
+        }
+        Some(Self {
+            host: host.to_string(),
+            port,
+            username,
+            password,
+        })
+    }
+
+    /// `ALL_PROXY` or `all_proxy` as set, when it names a SOCKS5 proxy.
+    pub fn env_spec() -> Option<String> {
+        ["ALL_PROXY", "all_proxy"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.trim().is_empty())
+            .filter(|value| Self::parse(value).is_some())
+    }
+
+    /// Connects to the proxy and asks it for a connection to `host:port`.
+    /// Failures of the proxy itself are `AppError::ProxyFailed`; the target
+    /// being down or refusing shows up as the usual connection errors.
+    pub fn connect(&self, host: &str, port: u16, timeout: Option<Duration>) -> Result<TcpStream, AppError> {
+        let mut stream = crate::connect_tcp(&self.host, self.port, timeout, None).map_err(|e| match e {
+            AppError::ConnectionFailed(msg) | AppError::TimedOut(msg) | AppError::ResolutionFailed(msg) => {
+                AppError::ProxyFailed(format!("could not reach {}:{}: {}", self.host, self.port, msg))
+            }
+            other => other,
+        })?;
+        let io = |e: std::io::Error| match e.kind() {
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => AppError::TimedOut("waiting for the proxy".to_string()),
+            _ => AppError::ProxyFailed(e.to_string()),
+        };
+        stream.set_read_timeout(timeout).map_err(io)?;
+        stream.set_write_timeout(timeout).map_err(io)?;
+
+        let methods: &[u8] = if self.username.is_some() { &[0x00, 0x02] } else { &[0x00] };
+        let mut greeting = vec![SOCKS_VERSION, methods.len() as u8];
+        greeting.extend_from_slice(methods);
+        stream.write_all(&greeting).map_err(io)?;
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply).map_err(io)?;
+        if reply[0] != SOCKS_VERSION {
+            return Err(AppError::ProxyFailed(format!("{}:{} is not a SOCKS5 proxy", self.host, self.port)));
+        }
+        match reply[1] {
+            0x00 => {}
+            0x02 => self.authenticate(&mut stream)?,
+            _ => return Err(AppError::ProxyFailed("no acceptable authentication method".to_string())),
+        }
+
+        let mut request = vec![SOCKS_VERSION, 0x01, 0x00];
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(0x01);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(0x04);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                let name = host.as_bytes();
+                if name.len() > u8::MAX as usize {
+                    return Err(AppError::ResolutionFailed(host.to_string()));
+                }
+                request.push(0x03);
+                request.push(name.len() as u8);
+                request.extend_from_slice(name);
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).map_err(io)?;
+
+        let mut head = [0u8; 4];
+        stream.read_exact(&mut head).map_err(io)?;
+        match head[1] {
+            0x00 => {}
+            0x03 | 0x04 => return Err(AppError::ConnectionFailed(format!("{} unreachable (reported by proxy)", host))),
+            0x05 => return Err(AppError::ConnectionFailed(format!("{} refused the connection (reported by proxy)", host))),
+            0x06 => return Err(AppError::TimedOut(format!("connecting to {} (reported by proxy)", host))),
+            0x02 => return Err(AppError::ProxyFailed("connection not allowed by the proxy's rules".to_string())),
+            code => return Err(AppError::ProxyFailed(format!("request failed with code {}", code))),
+        }
+        let bound_len = match head[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => {
+                let mut len = [0u8; 1];
+                stream.read_exact(&mut len).map_err(io)?;
+                len[0] as usize
+            }
+            _ => return Err(AppError::ProxyFailed("malformed reply".to_string())),
+        };
+        let mut bound = vec![0u8; bound_len + 2];
+        stream.read_exact(&mut bound).map_err(io)?;
+
+        // From here on libssh2 does its own timing.
+        stream.set_read_timeout(None).map_err(io)?;
+        stream.set_write_timeout(None).map_err(io)?;
+        Ok(stream)
+    }
+
+    /// Username/password authentication (RFC 1929).
+    fn authenticate(&self, stream: &mut TcpStream) -> Result<(), AppError> {
+        let username = self.username.as_deref().unwrap_or_default().as_bytes();
+        let password = self.password.as_deref().unwrap_or_default().as_bytes();
+        if username.len() > u8::MAX as usize || password.len() > u8::MAX as usize {
+            return Err(AppError::ProxyFailed("username or password too long".to_string()));
+        }
+        let mut request = vec![0x01, username.len() as u8];
+        request.extend_from_slice(username);
+        request.push(password.len() as u8);
+        request.extend_from_slice(password);
+        stream.write_all(&request).map_err(|e| AppError::ProxyFailed(e.to_string()))?;
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply).map_err(|e| AppError::ProxyFailed(e.to_string()))?;
+        if reply[1] != 0x00 {
+            return Err(AppError::ProxyFailed("the proxy rejected the username or password".to_string()));
+        }
+        Ok(())
+    }
+}
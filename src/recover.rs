@@ -0,0 +1,214 @@
+use std::path::{Path, PathBuf};
+use crate::SshConnection;
+
+/// A connection reconstructed from local evidence, with the places it was
+/// found in. Secrets cannot be recovered, so only the key path survives.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub host: String,
+    pub port: u16,
+    pub user: Option<String>,
+    pub key_path: Option<PathBuf>,
+    pub sources: Vec<String>,
+}
+
+impl Candidate {
+    pub fn name(&self) -> String {
+        let mut name = match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        };
+        if self.port != 22 {
+            name.push_str(&format!(":{}", self.port));
+        }
+        name
+    }
+
+    pub fn into_connection(self, default_user: &str) -> SshConnection {
+        SshConnection {
+            name: self.name(),
+            host: self.host,
+            port: self.port,
+            username: self.user.unwrap_or_else(|| default_user.to_string()),
+            key_path: self.key_path,
+            ..Default::default()
+        }
+    }
+}
+
+/// Hosts from a known_hosts file. Hashed entries and `@revoked` or
+/// `@cert-authority` lines carry no usable host name and are skipped.
+pub fn parse_known_hosts(content: &str, source: &str) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('|') || line.starts_with('@') {
+            continue;
+        }
+        let Some(hosts) = line.split_whitespace().next() else {
+            continue;
+        };
+        // Only the first name of `name,address` lists, so each key yields one entry.
+        let Some(host) = hosts.split(',').next() else {
+            continue;
+        };
+        let (host, port) = match host.strip_prefix('[').and_then(|rest| rest.split_once("]:")) {
+            Some((host, port)) => (host, port.parse().unwrap_or(22)),
+            None => (host, 22),
+        };
+        if host.contains(['*', '?', '!']) {
+            continue;
+        }
+        candidates.push(Candidate {
+            host: host.to_string(),
+            port,
+            user: None,
+            key_path: None,
+            sources: vec![source.to_string()],
+        });
+    }
+    candidates
+}
+
+/// `ssh` invocations from a bash or zsh history file.
+pub fn parse_shell_history(content: &str, source: &str) -> Vec<Candidate> {
+    content
+        .lines()
+        .filter_map(|line| {
+            // zsh extended history prefixes entries with `: <time>:<duration>;`.
+            let command = match line.strip_prefix(": ") {
+                Some(rest) => rest.split_once(';').map_or(rest, |(_, command)| command),
+                None => line,
+            };
+            parse_ssh_invocation(command, source)
+        })
+        .collect()
+}
+
+/// Parses `ssh [options] [user@]host [options] [command]`, keeping the destination,
+/// `-p`, `-l` and `-i`.
+fn parse_ssh_invocation(command: &str, source: &str) -> Option<Candidate> {
+    const OPTIONS_WITH_ARGUMENT: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+    let mut words = command.split_whitespace();
+    if words.next()? != "ssh" {
+        return None;
+    }
+    let mut port = None;
+    let mut user = None;
+    let mut key_path = None;
+    let mut destination = None;
+    while let Some(word) = words.next() {
+        // OpenSSH also takes options after the destination; the first
+        // plain word after it starts the remote command.
+        let Some(flags) = word.strip_prefix('-') else {
+            if destination.is_some() {
+                break;
+            }
+            destination = Some(word);
+            continue;
+        };
+        // Flags may be bundled (`-At`), and the last one may take its
+        // argument attached (`-p2222`) or as the next word.
+        for (i, flag) in flags.char_indices() {
+            if !OPTIONS_WITH_ARGUMENT.contains(flag) {
+                continue;
+            }
+            let attached = &flags[i + flag.len_utf8()..];
+            let value = if attached.is_empty() { words.next()? } else { attached };
+            match flag {
+                'p' => port = value.parse().ok(),
+                'l' => user = Some(value.to_string()),
+                'i' => key_path = Some(expand_tilde(value)),
+                _ => {}
+            }
+            break;
+        }
+    }
+
+    let destination = destination?.trim_start_matches("ssh://");
+    let (dest_user, host) = match destination.rsplit_once('@') {
+        Some((user, host)) => (Some(user.to_string()), host),
+        None => (None, destination),
+    };
+    let (host, dest_port) = match host.split_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => (host, port.parse().ok()),
+        _ => (host, None),
+    };
+    if host.is_empty() || host.contains(['$', '`', '"', '\'', '/']) {
+        return None;
+    }
+    Some(Candidate {
+        host: host.to_string(),
+        port: dest_port.or(port).unwrap_or(22),
+        user: dest_user.or(user),
+        key_path,
+        sources: vec![source.to_string()],
+    })
+}
+
+fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Folds duplicates together. Entries with the same host, port and user
+/// merge; a host seen without a user (as in known_hosts) merges into the
+/// first entry for that host and port.
+pub fn merge_candidates(candidates: Vec<Candidate>) -> Vec<Candidate> {
+    let mut merged: Vec<Candidate> = Vec::new();
+    let (with_user, without_user): (Vec<_>, Vec<_>) =
+        candidates.into_iter().partition(|candidate| candidate.user.is_some());
+    for candidate in with_user.into_iter().chain(without_user) {
+        let existing = merged.iter_mut().find(|other| {
+            other.host == candidate.host
+                && other.port == candidate.port
+                && (candidate.user.is_none() || other.user == candidate.user)
+        });
+        match existing {
+            Some(other) => {
+                if other.key_path.is_none() {
+                    other.key_path = candidate.key_path;
+                }
+                for source in candidate.sources {
+                    if !other.sources.contains(&source) {
+                        other.sources.push(source);
+                    }
+                }
+            }
+            None => merged.push(candidate),
+        }
+    }
+    merged
+}
+
+/// Gathers candidates from `~/.ssh/known_hosts` and the bash and zsh
+/// histories that can be read, skipping hosts that `existing` already has.
+pub fn find_candidates(home: &Path, existing: &[SshConnection]) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    type Parser = fn(&str, &str) -> Vec<Candidate>;
+    let sources: [(&str, Parser); 3] = [
+        (".ssh/known_hosts", parse_known_hosts),
+        (".bash_history", parse_shell_history),
+        (".zsh_history", parse_shell_history),
+    ];
+    for (file, parse) in sources {
+        // History files are not always valid UTF-8.
+        if let Ok(bytes) = std::fs::read(home.join(file)) {
+            candidates.extend(parse(&String::from_utf8_lossy(&bytes), &format!("~/{}", file)));
+        }
+    }
+
+    merge_candidates(candidates)
+        .into_iter()
+        .filter(|candidate| {
+            !existing.iter().any(|conn| {
+                conn.host == candidate.host
+                    && conn.port == candidate.port
+                    && candidate.user.as_ref().is_none_or(|user| *user == conn.username)
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,149 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::time::Duration;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use ssh2::{Channel, Session};
+use crate::{ssh_error, AppError};
+
+/// How long to wait for a key press before reading the channel again.
+const INPUT_POLL: Duration = Duration::from_millis(10);
+
+/// The built-in interactive client, for when `ssh` or `sshpass` cannot be
+/// started or the settings prefer it. Relays key presses to `channel`,
+/// which must have a PTY, and its output to the terminal until the server
+/// closes it. The terminal is in raw mode meanwhile, so Ctrl+C and the
+/// like reach the remote side. Returns the remote exit status.
+pub fn relay(sess: &Session, channel: &mut Channel) -> Result<i32, AppError> {
+    terminal::enable_raw_mode().map_err(terminal_error)?;
+    sess.set_blocking(false);
+    let result = relay_raw(sess, channel);
+    sess.set_blocking(true);
+    let _ = terminal::disable_raw_mode();
+    result?;
+    channel.wait_close().map_err(|e| ssh_error(e, AppError::ConnectionFailed))?;
+    channel.exit_status().map_err(|e| ssh_error(e, AppError::ConnectionFailed))
+}
+
+fn relay_raw(sess: &Session, channel: &mut Channel) -> Result<(), AppError> {
+    let mut stdout = io::stdout();
+    let mut buf = [0u8; 8192];
+    loop {
+        let mut idle = true;
+        for stream in [0, 1] {
+            let read = match stream {
+                0 => channel.read(&mut buf),
+                _ => channel.stderr().read(&mut buf),
+            };
+            match read {
+                Ok(0) => {}
+                Ok(n) => {
+                    stdout.write_all(&buf[..n]).map_err(terminal_error)?;
+                    idle = false;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(AppError::ConnectionFailed(e.to_string())),
+            }
+        }
+        stdout.flush().map_err(terminal_error)?;
+        if channel.eof() {
+            return Ok(());
+        }
+        // Only sends once the keepalive interval is due, if one is set.
+        let _ = sess.keepalive_send();
+
+        let wait = if idle { INPUT_POLL } else { Duration::ZERO };
+        while event::poll(wait).map_err(terminal_error)? {
+            match event::read().map_err(terminal_error)? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => {
+                    let bytes = key_bytes(key);
+                    if !bytes.is_empty() {
+                        blocking(sess, || channel.write_all(&bytes).and_then(|_| channel.flush()))
+                            .map_err(|e| AppError::ConnectionFailed(e.to_string()))?;
+                    }
+                }
+                Event::Paste(text) => {
+                    blocking(sess, || channel.write_all(text.as_bytes()).and_then(|_| channel.flush()))
+                        .map_err(|e| AppError::ConnectionFailed(e.to_string()))?;
+                }
+                Event::Resize(cols, rows) => {
+                    blocking(sess, || channel.request_pty_size(cols.into(), rows.into(), None, None))
+                        .map_err(|e| ssh_error(e, AppError::ConnectionFailed))?;
+                }
+                _ => {}
+            }
+            if wait.is_zero() {
+                break;
+            }
+        }
+    }
+}
+
+/// Runs `f` with the session blocking, so writes are never cut short.
+fn blocking<T>(sess: &Session, f: impl FnOnce() -> T) -> T {
+    sess.set_blocking(true);
+    let result = f();
+    sess.set_blocking(false);
+    result
+}
+
+fn terminal_error(e: io::Error) -> AppError {
+    AppError::ConnectionFailed(format!("Terminal error: {}", e))
+}
+
+/// The bytes an xterm sends for `key`.
+fn key_bytes(key: KeyEvent) -> Vec<u8> {
+    let control = key.modifiers.contains(KeyModifiers::CONTROL);
+    let sequence: &[u8] = match key.code {
+        KeyCode::Char(c) if control => {
+            let byte = match c {
+                'a'..='z' | 'A'..='Z' => c.to_ascii_lowercase() as u8 & 0x1f,
+                ' ' | '@' | '2' => 0,
+                '[' | '3' => 0x1b,
+                '\\' | '4' => 0x1c,
+                ']' | '5' => 0x1d,
+                '^' | '6' => 0x1e,
+                '_' | '7' | '/' => 0x1f,
+                _ => return with_alt(key, c.to_string().into_bytes()),
+            };
+            return with_alt(key, vec![byte]);
+        }
+        KeyCode::Char(c) => return with_alt(key, c.to_string().into_bytes()),
+        KeyCode::Enter => b"\r",
+        KeyCode::Tab => b"\t",
+        KeyCode::BackTab => b"\x1b[Z",
+        KeyCode::Backspace => b"\x7f",
+        KeyCode::Esc => b"\x1b",
+        KeyCode::Up => b"\x1b[A",
+        KeyCode::Down => b"\x1b[B",
+        KeyCode::Right => b"\x1b[C",
+        KeyCode::Left => b"\x1b[D",
+        KeyCode::Home => b"\x1b[H",
+        KeyCode::End => b"\x1b[F",
+        KeyCode::PageUp => b"\x1b[5~",
+        KeyCode::PageDown => b"\x1b[6~",
+        KeyCode::Insert => b"\x1b[2~",
+        KeyCode::Delete => b"\x1b[3~",
+        KeyCode::F(1) => b"\x1bOP",
+        KeyCode::F(2) => b"\x1bOQ",
+        KeyCode::F(3) => b"\x1bOR",
+        KeyCode::F(4) => b"\x1bOS",
+        KeyCode::F(5) => b"\x1b[15~",
+        KeyCode::F(6) => b"\x1b[17~",
+        KeyCode::F(7) => b"\x1b[18~",
+        KeyCode::F(8) => b"\x1b[19~",
+        KeyCode::F(9) => b"\x1b[20~",
+        KeyCode::F(10) => b"\x1b[21~",
+        KeyCode::F(11) => b"\x1b[23~",
+        KeyCode::F(12) => b"\x1b[24~",
+        _ => b"",
+    };
+    with_alt(key, sequence.to_vec())
+}
+
+/// Alt sends Esc before the key.
+fn with_alt(key: KeyEvent, mut bytes: Vec<u8>) -> Vec<u8> {
+    if key.modifiers.contains(KeyModifiers::ALT) && !bytes.is_empty() {
+        bytes.insert(0, 0x1b);
+    }
+    bytes
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use crate::{AppError, SshConnection};
+
+/// A scripted stand-in for real hosts, loaded with `--simulate`, so the
+/// connect and test flows can be exercised without SSH servers.
+#[derive(Debug, Deserialize)]
+pub struct Scenario {
+    /// Connections to show instead of the saved ones.
+    #[serde(default)]
+    pub connections: Vec<SshConnection>,
+    /// Outcome per connection name.
+    #[serde(default)]
+    pub outcomes: HashMap<String, Outcome>,
+    /// Outcome for connections not listed in `outcomes`.
+    #[serde(default)]
+    pub default: Outcome,
+    #[serde(skip)]
+    attempts: Mutex<HashMap<String, usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Outcome {
+    Success {
+        #[serde(default)]
+        delay_ms: u64,
+    },
+    AuthFailure {
+        #[serde(default)]
+        delay_ms: u64,
+    },
+    Unreachable {
+        #[serde(default)]
+        delay_ms: u64,
+    },
+    /// Waits out whatever timeout the caller gave.
+    Timeout,
+    /// Fails every `fail_every`-th attempt and succeeds otherwise.
+    Flaky {
+        #[serde(default)]
+        delay_ms: u64,
+        fail_every: usize,
+    },
+}
+
+impl Default for Outcome {
+    fn default() -> Self {
+        Outcome::Success { delay_ms: 0 }
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Invalid scenario {}", path.display()))
+    }
+
+    /// Plays the scripted outcome for `conn` in place of
+    /// `probe_connection`, blocking for as long as the script says.
+    pub fn probe(&self, conn: &SshConnection, timeout: Option<Duration>) -> Result<(), AppError> {
+        let attempt = {
+            let mut attempts = self.attempts.lock().unwrap_or_else(|e| e.into_inner());
+            let attempt = attempts.entry(conn.name.clone()).or_insert(0);
+            *attempt += 1;
+            *attempt
+        };
+        let outcome = self.outcomes.get(&conn.name).unwrap_or(&self.default);
+        let sleep_ms = |delay_ms: u64| thread::sleep(Duration::from_millis(delay_ms));
+        match *outcome {
+            Outcome::Success { delay_ms } => {
+                sleep_ms(delay_ms);
+                Ok(())
+            }
+            Outcome::AuthFailure { delay_ms } => {
+                sleep_ms(delay_ms);
+                Err(AppError::AuthenticationFailed("simulated authentication failure".to_string()))
+            }
+            Outcome::Unreachable { delay_ms } => {
+                sleep_ms(delay_ms);
+                Err(AppError::ConnectionFailed("simulated: connection refused".to_string()))
+            }
+            Outcome::Timeout => {
+                thread::sleep(timeout.unwrap_or(Duration::from_secs(10)));
+                Err(AppError::TimedOut(format!("simulated timeout connecting to {}", conn.host)))
+            }
+            Outcome::Flaky { delay_ms, fail_every } => {
+                sleep_ms(delay_ms);
+                if fail_every > 0 && attempt % fail_every == 0 {
+                    Err(AppError::ConnectionFailed("simulated: connection reset".to_string()))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn scenario(json: &str) -> Scenario {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn conn(name: &str) -> SshConnection {
+        SshConnection { name: name.to_string(), host: format!("{}.test", name), ..SshConnection::default() }
+    }
+
+    #[test]
+    fn shipped_fixtures_load() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+        let demo = Scenario::load(&dir.join("demo.json")).unwrap();
+        assert_eq!(demo.connections.len(), 6);
+        assert_eq!(demo.outcomes["staging"], Outcome::AuthFailure { delay_ms: 600 });
+        assert_eq!(demo.outcomes["legacy-box"], Outcome::Timeout);
+        assert_eq!(demo.default, Outcome::Success { delay_ms: 0 });
+
+        let flaky = Scenario::load(&dir.join("flaky.json")).unwrap();
+        assert_eq!(flaky.outcomes["flaky-2"], Outcome::Flaky { delay_ms: 900, fail_every: 3 });
+        assert_eq!(flaky.default, Outcome::Success { delay_ms: 250 });
+    }
+
+    #[test]
+    fn outcomes_map_to_app_errors() {
+        let scenario = scenario(
+            r#"{"outcomes": {
+                "auth": {"result": "auth_failure"},
+                "down": {"result": "unreachable"},
+                "slow": {"result": "success", "delay_ms": 50}
+            }}"#,
+        );
+        assert!(matches!(scenario.probe(&conn("auth"), None), Err(AppError::AuthenticationFailed(_))));
+        assert!(matches!(scenario.probe(&conn("down"), None), Err(AppError::ConnectionFailed(_))));
+        assert!(scenario.probe(&conn("unlisted"), None).is_ok());
+
+        let started = Instant::now();
+        assert!(scenario.probe(&conn("slow"), None).is_ok());
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn timeouts_wait_out_the_given_timeout() {
+        let scenario = scenario(r#"{"default": {"result": "timeout"}}"#);
+        let started = Instant::now();
+        let err = scenario.probe(&conn("box"), Some(Duration::from_millis(30))).unwrap_err();
+        assert!(started.elapsed() >= Duration::from_millis(30));
+        assert!(matches!(err, AppError::TimedOut(ref msg) if msg.contains("box.test")), "{}", err);
+    }
+
+    #[test]
+    fn flaky_fails_every_nth_attempt_per_connection() {
+        let scenario = scenario(r#"{"default": {"result": "flaky", "fail_every": 3}}"#);
+        let results: Vec<bool> = (0..6).map(|_| scenario.probe(&conn("a"), None).is_ok()).collect();
+        assert_eq!(results, [true, true, false, true, true, false]);
+        // Another connection counts its own attempts.
+        assert!(scenario.probe(&conn("b"), None).is_ok());
+
+        let never = self::scenario(r#"{"default": {"result": "flaky", "fail_every": 0}}"#);
+        assert!((0..4).all(|_| never.probe(&conn("a"), None).is_ok()));
+    }
+
+    #[test]
+    fn unknown_outcomes_are_rejected() {
+        assert!(serde_json::from_str::<Scenario>(r#"{"default": {"result": "explode"}}"#).is_err());
+        assert!(serde_json::from_str::<Scenario>(r#"{"default": {"result": "flaky"}}"#).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::{HostKeyPolicy, JumpHost, SshConnection};
+
+pub const DEFAULT_SSH: &str = "ssh";
+pub const DEFAULT_SSHPASS: &str = "sshpass";
+
+/// The local `ssh` client, as reported by `ssh -V`, so the command line
+/// can avoid options it would reject.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshClient {
+    /// The `ssh` to start: a name looked up on `PATH`, or a path.
+    pub program: String,
+    /// The `sshpass` to start, likewise.
+    pub sshpass_program: String,
+    /// The banner as printed, e.g. `OpenSSH_9.2p1 Debian-2, OpenSSL 3.0.11`.
+    pub banner: Option<String>,
+    /// OpenSSH major and minor version, when the banner names one.
+    pub version: Option<(u32, u32)>,
+    /// Whether `sshpass` can be started. Without it, as on Windows,
+    /// password logins go through the built-in client and ssh asks for
+    /// key passphrases itself.
+    pub sshpass: bool,
+}
+
+impl Default for SshClient {
+    fn default() -> Self {
+        Self {
+            program: DEFAULT_SSH.to_string(),
+            sshpass_program: DEFAULT_SSHPASS.to_string(),
+            banner: None,
+            version: None,
+            sshpass: false,
+        }
+    }
+}
+
+impl SshClient {
+    /// Runs `<program> -V`, which prints its banner on stderr, and checks
+    /// that `sshpass_program` can be started.
+    pub fn detect(program: &str, sshpass_program: &str) -> Self {
+        let mut client = match Command::new(program).arg("-V").output() {
+            Ok(output) => {
+                let banner = String::from_utf8_lossy(&output.stderr);
+                Self::parse(banner.trim())
+            }
+            Err(_) => Self::default(),
+        };
+        client.program = program.to_string();
+        client.sshpass_program = sshpass_program.to_string();
+        client.sshpass = Command::new(sshpass_program).arg("-V").output().is_ok();
+        client
+    }
+
+    /// What is wrong with the programs chosen in settings.json. The
+    /// defaults are not checked, since missing ones have fallbacks.
+    pub fn program_problems(&self) -> Vec<String> {
+        [("ssh_binary", &self.program, DEFAULT_SSH), ("sshpass_binary", &self.sshpass_program, DEFAULT_SSHPASS)]
+            .into_iter()
+            .filter(|(_, program, default)| program != default)
+            .filter_map(|(setting, program, _)| {
+                let problem = match resolve_program(program) {
+                    None => "was not found",
+                    Some(path) if !is_executable(&path) => "is not executable",
+                    Some(_) => return None,
+                };
+                Some(format!("{} {} (set as {} in settings.json)", program, problem, setting))
+            })
+            .collect()
+    }
+
+    pub fn parse(banner: &str) -> Self {
+        let version = banner
+            .split_once("OpenSSH_")
+            .and_then(|(_, rest)| {
+                let (major, rest) = rest.split_once('.')?;
+                let minor: String = rest.chars().take_while(char::is_ascii_digit).collect();
+                Some((major.parse().ok()?, minor.parse().ok()?))
+            });
+        Self {
+            banner: Some(banner.to_string()).filter(|banner| !banner.is_empty()),
+            version,
+            ..Self::default()
+        }
+    }
+
+    /// `-J` arrived in OpenSSH 7.3. Clients of unknown version get the
+    /// benefit of the doubt.
+    pub fn supports_proxy_jump(&self) -> bool {
+        self.version.is_none_or(|version| version >= (7, 3))
+    }
+
+    /// `StrictHostKeyChecking=accept-new` arrived in OpenSSH 7.6.
+    pub fn supports_accept_new(&self) -> bool {
+        self.version.is_none_or(|version| version >= (7, 6))
+    }
+
+    pub fn label(&self) -> String {
+        match (self.version, &self.banner) {
+            (Some((major, minor)), _) => format!("OpenSSH {}.{}", major, minor),
+            (None, Some(banner)) => banner.clone(),
+            (None, None) => "not found".to_string(),
+        }
+    }
+
+    /// The `ProxyCommand` standing in for `-J` on clients without it, or
+    /// `None` when the jump host cannot be expressed that way (chains of
+    /// several jump hosts).
+    pub fn proxy_command(jump_host: &str) -> Option<String> {
+        if jump_host.contains(',') {
+            return None;
+        }
+        let jump = JumpHost::parse(jump_host)?;
+        let mut command = String::from("ssh -W %h:%p");
+        if jump.port != 22 {
+            command.push_str(&format!(" -p {}", jump.port));
+        }
+        if let Some(user) = &jump.user {
+            command.push_str(&format!(" -l {}", user));
+        }
+        command.push_str(&format!(" {}", jump.host));
+        Some(command)
+    }
+
+    /// Settings of `conn` that this client cannot honour and that are
+    /// therefore left out of the command line.
+    pub fn unsupported_options(&self, conn: &SshConnection) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Some(jump_host) = &conn.jump_host {
+            if !self.supports_proxy_jump() && Self::proxy_command(jump_host).is_none() {
+                warnings.push(format!(
+                    "{} does not support -J and the jump host '{}' cannot be turned into a ProxyCommand; connecting directly",
+                    self.label(),
+                    jump_host
+                ));
+            }
+        }
+        if conn.host_key_policy == Some(HostKeyPolicy::AcceptNew) && !self.supports_accept_new() {
+            warnings.push(format!(
+                "{} does not support StrictHostKeyChecking=accept-new; ssh asks about unknown host keys instead",
+                self.label()
+            ));
+        }
+        warnings
+    }
+}
+
+/// Where `program` would be started from: itself when it is a path,
+/// otherwise the first match on `PATH`.
+fn resolve_program(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.exists().then(|| path.to_path_buf());
+    }
+    let names: Vec<String> = if cfg!(windows) && path.extension().is_none() {
+        vec![program.to_string(), format!("{}.exe", program)]
+    } else {
+        vec![program.to_string()]
+    };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
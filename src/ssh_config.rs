@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+use crate::{HostKeyPolicy, SshConnection};
+
+/// A concrete `Host` entry from an OpenSSH client config.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SshConfigHost {
+    pub alias: String,
+    pub host_name: Option<String>,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    pub identity_file: Option<PathBuf>,
+    pub proxy_jump: Option<String>,
+    /// Forwards in `-L`/`-R` argument form, `[bind:]listen:host:port`.
+    pub local_forwards: Vec<String>,
+    pub remote_forwards: Vec<String>,
+}
+
+/// Parses the `Host` blocks of an OpenSSH config. Patterns containing
+/// wildcards or negations are skipped, as are `Match` blocks; a `Host` line
+/// naming several aliases yields one entry per alias.
+pub fn parse_ssh_config(content: &str) -> Vec<SshConfigHost> {
+    let mut hosts = Vec::new();
+    let mut current: Vec<SshConfigHost> = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (keyword, value) = match line.split_once(|c: char| c.is_whitespace() || c == '=') {
+            Some((keyword, value)) => (keyword, value.trim().trim_start_matches('=').trim()),
+            None => (line, ""),
+        };
+        let value = value.trim_matches('"');
+
+        match keyword.to_ascii_lowercase().as_str() {
+            "host" => {
+                hosts.append(&mut current);
+                current = value
+                    .split_whitespace()
+                    .filter(|alias| !alias.contains(['*', '?', '!']))
+                    .map(|alias| SshConfigHost {
+                        alias: alias.to_string(),
+                        host_name: None,
+                        port: None,
+                        user: None,
+                        identity_file: None,
+                        proxy_jump: None,
+                        local_forwards: Vec::new(),
+                        remote_forwards: Vec::new(),
+                    })
+                    .collect();
+            }
+            "match" => hosts.append(&mut current),
+            "hostname" => current.iter_mut().for_each(|h| {
+                h.host_name.get_or_insert_with(|| value.to_string());
+            }),
+            "port" => {
+                if let Ok(port) = value.parse() {
+                    current.iter_mut().for_each(|h| {
+                        h.port.get_or_insert(port);
+                    });
+                }
+            }
+            "user" => current.iter_mut().for_each(|h| {
+                h.user.get_or_insert_with(|| value.to_string());
+            }),
+            "identityfile" => current.iter_mut().for_each(|h| {
+                h.identity_file.get_or_insert_with(|| expand_tilde(value));
+            }),
+            "proxyjump" => current.iter_mut().for_each(|h| {
+                h.proxy_jump.get_or_insert_with(|| value.to_string());
+            }),
+            "localforward" | "remoteforward" => {
+                // Unlike most keywords, every forward line applies.
+                let Some((listen, target)) = value.split_once(char::is_whitespace) else {
+                    continue;
+                };
+                let forward = format!("{}:{}", listen, target.trim());
+                for h in current.iter_mut() {
+                    let forwards = if keyword.eq_ignore_ascii_case("localforward") {
+                        &mut h.local_forwards
+                    } else {
+                        &mut h.remote_forwards
+                    };
+                    forwards.push(forward.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+    hosts.append(&mut current);
+    hosts
+}
+
+pub(crate) fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Renders connections as OpenSSH `Host` stanzas. Passwords have no config
+/// equivalent, so connections relying on one get a comment instead.
+pub fn format_ssh_config(connections: &[SshConnection]) -> String {
+    let mut out = String::from("# Generated by peroxide\n");
+    for conn in connections {
+        let alias: String = conn.name
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-");
+        out.push('\n');
+        out.push_str(&format!("Host {}\n", alias));
+        out.push_str(&format!("    HostName {}\n", conn.host));
+        out.push_str(&format!("    Port {}\n", conn.port));
+        out.push_str(&format!("    User {}\n", conn.username));
+        if let Some(jump_host) = &conn.jump_host {
+            out.push_str(&format!("    ProxyJump {}\n", jump_host));
+        }
+        for (keyword, forwards) in [("LocalForward", &conn.local_forwards), ("RemoteForward", &conn.remote_forwards)] {
+            for forward in forwards {
+                // `[bind:]listen:host:port` becomes `[bind:]listen host:port`.
+                let mut parts = forward.rsplitn(3, ':');
+                if let (Some(port), Some(host), Some(listen)) = (parts.next(), parts.next(), parts.next()) {
+                    out.push_str(&format!("    {} {} {}:{}\n", keyword, listen, host, port));
+                }
+            }
+        }
+        for option in &conn.ssh_options {
+            if let Some((key, value)) = option.split_once('=') {
+                out.push_str(&format!("    {} {}\n", key, value));
+            }
+        }
+        for option in conn.host_key_policy.map(HostKeyPolicy::ssh_options).unwrap_or_default() {
+            if let Some((key, value)) = option.split_once('=') {
+                out.push_str(&format!("    {} {}\n", key, value));
+            }
+        }
+        if let Some(key_path) = &conn.key_path {
+            let key_path = key_path.display().to_string();
+            if key_path.contains(char::is_whitespace) {
+                out.push_str(&format!("    IdentityFile \"{}\"\n", key_path));
+            } else {
+                out.push_str(&format!("    IdentityFile {}\n", key_path));
+            }
+        } else if conn.has_password() {
+            out.push_str("    # Uses password authentication; the password is not exported\n");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_blocks_become_entries_per_alias() {
+        let hosts = parse_ssh_config(
+            "# global\n\
+             User nobody\n\
+             \n\
+             Host web web-alt\n\
+             \x20   HostName 10.0.0.5\n\
+             \x20   port=2222\n\
+             \x20   USER deploy\n\
+             \x20   User ignored\n\
+             Host *.internal !bastion db\n\
+             \x20   IdentityFile \"/keys/db key\"\n\
+             \x20   ProxyJump bastion\n",
+        );
+        let aliases: Vec<&str> = hosts.iter().map(|h| h.alias.as_str()).collect();
+        assert_eq!(aliases, ["web", "web-alt", "db"]);
+        assert_eq!(hosts[0].host_name.as_deref(), Some("10.0.0.5"));
+        assert_eq!(hosts[1].port, Some(2222));
+        assert_eq!(hosts[1].user.as_deref(), Some("deploy"), "the first value wins");
+        assert_eq!(hosts[2].identity_file, Some(PathBuf::from("/keys/db key")));
+        assert_eq!(hosts[2].proxy_jump.as_deref(), Some("bastion"));
+        assert_eq!(hosts[2].user, None, "settings before the first Host belong to no entry");
+    }
+
+    #[test]
+    fn match_blocks_and_bad_values_are_ignored() {
+        let hosts = parse_ssh_config("Host web\n  Port nope\nMatch host web\n  User root\nHost *\n  User everyone\n");
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].port, None);
+        assert_eq!(hosts[0].user, None);
+    }
+
+    #[test]
+    fn every_forward_line_applies() {
+        let hosts = parse_ssh_config(
+            "Host tunnel\n  LocalForward 8080 localhost:80\n  LocalForward 127.0.0.1:5432 db:5432\n  RemoteForward 9000 localhost:9000\n",
+        );
+        assert_eq!(hosts[0].local_forwards, ["8080:localhost:80", "127.0.0.1:5432:db:5432"]);
+        assert_eq!(hosts[0].remote_forwards, ["9000:localhost:9000"]);
+    }
+
+    #[test]
+    fn tilde_expands_to_the_home_directory() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_tilde("~/.ssh/id_ed25519"), home.join(".ssh/id_ed25519"));
+        assert_eq!(expand_tilde("/etc/key"), PathBuf::from("/etc/key"));
+        assert_eq!(expand_tilde("~other/key"), PathBuf::from("~other/key"));
+    }
+}
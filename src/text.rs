@@ -0,0 +1,134 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const ELLIPSIS: &str = "…";
+
+/// Number of terminal cells `text` occupies; wide characters such as CJK or
+/// emoji count as two.
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+/// Shortens `text` to at most `max` cells, ending in an ellipsis when
+/// anything was cut. Grapheme clusters are never split.
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.width() <= max {
+        return text.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let budget = max - ELLIPSIS.width();
+    let mut out = String::new();
+    let mut used = 0;
+    for grapheme in text.graphemes(true) {
+        let width = grapheme.width();
+        if used + width > budget {
+            break;
+        }
+        out.push_str(grapheme);
+        used += width;
+    }
+    out.push_str(ELLIPSIS);
+    out
+}
+
+/// Truncates `text` to `width` cells and pads it with spaces so it fills
+/// exactly that many.
+pub fn pad(text: &str, width: usize) -> String {
+    let mut out = truncate(text, width);
+    let fill = width.saturating_sub(out.width());
+    out.extend(std::iter::repeat_n(' ', fill));
+    out
+}
+
+/// `text` without ANSI escape sequences (colors, cursor movement, window
+/// titles) and other control characters, with tabs expanded to the next
+/// multiple of 8 cells, so it is safe to draw as plain text.
+pub fn strip_control(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters and intermediates up to a final byte.
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC, DCS and friends: up to BEL or ESC \.
+                Some(']' | 'P' | 'X' | '^' | '_') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\t' => {
+                let fill = 8 - out.width() % 8;
+                out.extend(std::iter::repeat_n(' ', fill));
+            }
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_characters_take_two_cells() {
+        assert_eq!(display_width("web"), 3);
+        assert_eq!(display_width("東京"), 4);
+        assert_eq!(display_width("🚀"), 2);
+        assert_eq!(display_width("e\u{301}"), 1);
+    }
+
+    #[test]
+    fn truncate_counts_cells_not_chars() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("database-primary", 8), "databas…");
+        assert_eq!(truncate("東京サーバー", 7), "東京サ…");
+        // A wide character that would straddle the limit is left out.
+        assert_eq!(truncate("東京サーバー", 6), "東京…");
+        assert_eq!(truncate("anything", 0), "");
+        for max in 1..12 {
+            assert!(display_width(&truncate("東京-web-サーバー", max)) <= max);
+        }
+    }
+
+    #[test]
+    fn truncate_never_splits_grapheme_clusters() {
+        // e + combining acute, and a family emoji joined with ZWJs.
+        assert_eq!(truncate("cafe\u{301} au lait", 5), "cafe\u{301}…");
+        let family = "👨\u{200d}👩\u{200d}👧";
+        let cut = truncate(&format!("{}{}", family, "abcdef"), 4);
+        assert_eq!(cut, format!("{}a…", family));
+    }
+
+    #[test]
+    fn pad_fills_exactly_the_width() {
+        for name in ["web", "東京", "🚀 deploy", "a-very-long-connection-name"] {
+            assert_eq!(display_width(&pad(name, 10)), 10, "{}", name);
+        }
+        assert_eq!(pad("東京", 5), "東京 ");
+    }
+
+    #[test]
+    fn strip_control_removes_escapes_and_expands_tabs() {
+        assert_eq!(strip_control("\x1b[31mred\x1b[0m"), "red");
+        assert_eq!(strip_control("\x1b]0;title\x07after"), "after");
+        assert_eq!(strip_control("\x1b]0;title\x1b\\after"), "after");
+        assert_eq!(strip_control("a\tb"), "a       b");
+        assert_eq!(strip_control("東\tb"), "東      b");
+        assert_eq!(strip_control("bell\x07"), "bell");
+    }
+}
@@ -0,0 +1,253 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::text;
+
+/// What the caller should do once the viewer has handled a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewerAction {
+    Continue,
+    Close,
+    /// Copy `TextViewer::text` to the clipboard.
+    CopyAll,
+}
+
+/// A slice `start..end` (bytes) of line `line`: one screen row, or a
+/// search hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextRange {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A read-only text popup with scrolling, line wrapping and search. It
+/// handles its own keys, so every popup built on it behaves the same:
+/// arrows, PgUp/PgDn and g/G scroll, `w` toggles wrapping, `/` searches
+/// (smart case), `n`/`N` go to the next or previous hit, `y` copies
+/// everything and Esc or `q` closes.
+#[derive(Debug, Clone)]
+pub struct TextViewer {
+    pub title: String,
+    lines: Vec<String>,
+    pub wrap: bool,
+    /// The first visible row.
+    pub scroll: usize,
+    /// The query while it is typed after `/`.
+    pub search_input: Option<String>,
+    query: String,
+    /// Index into `matches()` of the hit `n`/`N` last moved to.
+    current: Option<usize>,
+    /// Size of the text area at the last render, set with `set_viewport`.
+    width: usize,
+    height: usize,
+}
+
+impl TextViewer {
+    pub fn new(title: impl Into<String>, content: &str) -> Self {
+        Self {
+            title: title.into(),
+            lines: content.lines().map(str::to_string).collect(),
+            wrap: true,
+            scroll: 0,
+            search_input: None,
+            query: String::new(),
+            current: None,
+            width: 80,
+            height: 20,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn line(&self, index: usize) -> &str {
+        self.lines.get(index).map(String::as_str).unwrap_or_default()
+    }
+
+    /// The last search, empty when there is none.
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Starts at the end, for logs whose newest entries are last.
+    pub fn scrolled_to_bottom(mut self) -> Self {
+        self.scroll = usize::MAX;
+        self
+    }
+
+    /// Records the size of the text area, which scrolling and wrapping
+    /// depend on. Call on every render.
+    pub fn set_viewport(&mut self, width: u16, height: u16) {
+        self.width = (width as usize).max(1);
+        self.height = (height as usize).max(1);
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    /// The screen rows: one per line, or several per line while wrapping.
+    /// Wrapping breaks at the text area's width without splitting
+    /// grapheme clusters.
+    pub fn rows(&self) -> Vec<TextRange> {
+        let mut rows = Vec::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            if !self.wrap {
+                rows.push(TextRange { line: index, start: 0, end: line.len() });
+                continue;
+            }
+            let mut start = 0;
+            let mut width = 0;
+            for (offset, grapheme) in line.grapheme_indices(true) {
+                let cells = text::display_width(grapheme);
+                if width + cells > self.width && offset > start {
+                    rows.push(TextRange { line: index, start, end: offset });
+                    start = offset;
+                    width = 0;
+                }
+                width += cells;
+            }
+            rows.push(TextRange { line: index, start, end: line.len() });
+        }
+        rows
+    }
+
+    /// Every hit of the query, in order. Matching ignores ASCII case
+    /// unless the query has an uppercase letter.
+    pub fn matches(&self) -> Vec<TextRange> {
+        if self.query.is_empty() {
+            return Vec::new();
+        }
+        let fold = !self.query.chars().any(char::is_uppercase);
+        let query = if fold { self.query.to_ascii_lowercase() } else { self.query.clone() };
+        let mut matches = Vec::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            let haystack = if fold { line.to_ascii_lowercase() } else { line.clone() };
+            let mut from = 0;
+            while let Some(pos) = haystack[from..].find(&query) {
+                let start = from + pos;
+                matches.push(TextRange { line: index, start, end: start + query.len() });
+                from = start + query.len();
+            }
+        }
+        matches
+    }
+
+    /// The hit `n`/`N` last moved to, with its position among all hits.
+    pub fn current_match(&self) -> Option<(usize, TextRange)> {
+        let index = self.current?;
+        self.matches().get(index).map(|span| (index, *span))
+    }
+
+    /// Adds lines at the end, dropping the oldest beyond `limit`. A viewer
+    /// scrolled to the bottom stays there, so new lines come into view.
+    pub fn append(&mut self, lines: Vec<String>, limit: usize) {
+        if lines.is_empty() {
+            return;
+        }
+        let at_bottom = self.scroll >= self.max_scroll();
+        self.lines.extend(lines);
+        let excess = self.lines.len().saturating_sub(limit);
+        if excess > 0 {
+            let dropped_rows = self.rows().iter().take_while(|row| row.line < excess).count();
+            self.lines.drain(..excess);
+            self.scroll = self.scroll.saturating_sub(dropped_rows);
+            self.current = None;
+        }
+        if at_bottom {
+            self.scroll = usize::MAX;
+        }
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.rows().len().saturating_sub(self.height)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> ViewerAction {
+        if let Some(input) = &mut self.search_input {
+            match key.code {
+                KeyCode::Esc => self.search_input = None,
+                KeyCode::Enter => {
+                    self.query = self.search_input.take().unwrap_or_default();
+                    self.current = None;
+                    self.next_match(true);
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => input.push(c),
+                _ => {}
+            }
+            return ViewerAction::Continue;
+        }
+
+        let page = self.height.saturating_sub(1).max(1);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return ViewerAction::Close,
+            KeyCode::Char('y') => return ViewerAction::CopyAll,
+            KeyCode::Up | KeyCode::Char('k') => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll += 1,
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(page),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll += page,
+            KeyCode::Home | KeyCode::Char('g') => self.scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => self.scroll = usize::MAX,
+            KeyCode::Char('w') => self.toggle_wrap(),
+            KeyCode::Char('/') => self.search_input = Some(String::new()),
+            KeyCode::Char('n') => self.next_match(true),
+            KeyCode::Char('N') => self.next_match(false),
+            _ => {}
+        }
+        self.scroll = self.scroll.min(self.max_scroll());
+        ViewerAction::Continue
+    }
+
+    /// Keeps the line at the top of the screen there.
+    fn toggle_wrap(&mut self) {
+        let top_line = self.rows().get(self.scroll).map_or(0, |row| row.line);
+        self.wrap = !self.wrap;
+        self.scroll = self.rows().iter().position(|row| row.line == top_line).unwrap_or(0);
+    }
+
+    /// Moves to the next (or previous) hit, wrapping around at the ends.
+    /// Without a current hit, starts from the top of the screen.
+    fn next_match(&mut self, forward: bool) {
+        let matches = self.matches();
+        if matches.is_empty() {
+            self.current = None;
+            return;
+        }
+        let next = match self.current {
+            Some(index) if forward => (index + 1) % matches.len(),
+            Some(index) => index.checked_sub(1).unwrap_or(matches.len() - 1),
+            None => {
+                let rows = self.rows();
+                let top = rows.get(self.scroll).map_or((0, 0), |row| (row.line, row.start));
+                let first = matches.iter().position(|hit| (hit.line, hit.start) >= top);
+                match (first, forward) {
+                    (Some(index), true) => index,
+                    (None, true) => 0,
+                    (Some(index), false) => index.checked_sub(1).unwrap_or(matches.len() - 1),
+                    (None, false) => matches.len() - 1,
+                }
+            }
+        };
+        self.current = Some(next);
+        self.reveal(matches[next]);
+    }
+
+    /// Scrolls just enough for the row holding the start of `hit` to show.
+    fn reveal(&mut self, hit: TextRange) {
+        let rows = self.rows();
+        let Some(row) = rows
+            .iter()
+            .position(|row| row.line == hit.line && row.start <= hit.start && (hit.start < row.end || row.start == row.end))
+            .or_else(|| rows.iter().rposition(|row| row.line == hit.line))
+        else {
+            return;
+        };
+        if row < self.scroll {
+            self.scroll = row;
+        } else if row >= self.scroll + self.height {
+            self.scroll = row + 1 - self.height;
+        }
+    }
+}
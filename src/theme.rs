@@ -0,0 +1,115 @@
+use std::fs;
+use std::str::FromStr;
+use anyhow::{Context, Result};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders};
+use serde::Deserialize;
+
+/// Colors the UI is drawn with, read from `theme.toml` in the config
+/// directory. Colors are names (`yellow`, `darkgray`), `#rrggbb` or a
+/// 256-color index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// Search matches, the active form field and dialog borders.
+    pub highlight: Color,
+    pub error: Color,
+    pub border: Color,
+    /// The selected list row; without either color it is shown reversed.
+    pub selected_fg: Option<Color>,
+    pub selected_bg: Option<Color>,
+    /// Secondary text such as notes, badges and key sources.
+    pub dim: Color,
+    /// The focused button of a confirmation dialog.
+    pub button: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            highlight: Color::Yellow,
+            error: Color::Red,
+            border: Color::Reset,
+            selected_fg: None,
+            selected_bg: None,
+            dim: Color::DarkGray,
+            button: Color::Green,
+        }
+    }
+}
+
+/// `theme.toml` as written; every key is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeFile {
+    highlight: Option<String>,
+    error: Option<String>,
+    border: Option<String>,
+    selected_fg: Option<String>,
+    selected_bg: Option<String>,
+    dim: Option<String>,
+    button: Option<String>,
+}
+
+impl Theme {
+    /// Loads `theme.toml`, falling back to the default theme when there is
+    /// none.
+    pub fn load() -> Result<Self> {
+        let theme_file = dirs::config_dir()
+            .context("Could not find config directory")?
+            .join("peroxide")
+            .join("theme.toml");
+        if !theme_file.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&theme_file)
+            .with_context(|| format!("Could not read {}", theme_file.display()))?;
+        Self::parse(&content).with_context(|| format!("Invalid {}", theme_file.display()))
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let file: ThemeFile = toml::from_str(content)?;
+        let defaults = Self::default();
+        let color = |key: &str, value: Option<String>, default: Color| -> Result<Color> {
+            match value {
+                Some(value) => Color::from_str(&value).map_err(|_| anyhow::anyhow!("{}: unknown color '{}'", key, value)),
+                None => Ok(default),
+            }
+        };
+        Ok(Self {
+            highlight: color("highlight", file.highlight, defaults.highlight)?,
+            error: color("error", file.error, defaults.error)?,
+            border: color("border", file.border, defaults.border)?,
+            selected_fg: file.selected_fg.map(|c| color("selected_fg", Some(c), Color::Reset)).transpose()?,
+            selected_bg: file.selected_bg.map(|c| color("selected_bg", Some(c), Color::Reset)).transpose()?,
+            dim: color("dim", file.dim, defaults.dim)?,
+            button: color("button", file.button, defaults.button)?,
+        })
+    }
+
+    /// A bordered block in the theme's border color.
+    pub fn block(&self) -> Block<'static> {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(self.border))
+    }
+
+    pub fn selected(&self) -> Style {
+        match (self.selected_fg, self.selected_bg) {
+            (None, None) => Style::default().add_modifier(Modifier::REVERSED),
+            (fg, bg) => {
+                let mut style = Style::default();
+                if let Some(fg) = fg {
+                    style = style.fg(fg);
+                }
+                if let Some(bg) = bg {
+                    style = style.bg(bg);
+                }
+                style
+            }
+        }
+    }
+
+    pub fn dim(&self) -> Style {
+        Style::default().fg(self.dim)
+    }
+}
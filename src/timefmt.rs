@@ -0,0 +1,219 @@
+use std::time::Duration;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The absolute format used when the configured one is missing or invalid.
+pub const DEFAULT_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// How timestamps such as "last connected" are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampStyle {
+    #[default]
+    Relative,
+    Absolute,
+}
+
+impl TimestampStyle {
+    pub fn next(self) -> Self {
+        match self {
+            TimestampStyle::Relative => TimestampStyle::Absolute,
+            TimestampStyle::Absolute => TimestampStyle::Relative,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TimestampStyle::Relative => "relative",
+            TimestampStyle::Absolute => "absolute",
+        }
+    }
+}
+
+/// A compact relative time such as "just now", "5m ago", "yesterday" or
+/// "3d ago". Times slightly in the future, as clock skew produces, count
+/// as just now.
+pub fn relative(then: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = (now - then).num_seconds();
+    match secs {
+        ..-60 => "in the future".to_string(),
+        -60..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        86400..172800 => "yesterday".to_string(),
+        172800..604800 => format!("{}d ago", secs / 86400),
+        604800..31536000 => format!("{}w ago", secs / 604800),
+        _ => format!("{}y ago", secs / 31536000),
+    }
+}
+
+/// `then` in local time using a strftime `pattern`, falling back to
+/// `DEFAULT_TIMESTAMP_FORMAT` when the pattern does not parse.
+pub fn absolute(then: DateTime<Utc>, pattern: &str) -> String {
+    let pattern = if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+        DEFAULT_TIMESTAMP_FORMAT
+    } else {
+        pattern
+    };
+    then.with_timezone(&Local).format(pattern).to_string()
+}
+
+/// A duration in at most two units: "45s", "3m 12s", "1h 12m", "2d 3h".
+pub fn duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {}s", secs / 60, secs % 60),
+        3600..86400 => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+/// Reads an expiry typed in the connection form: an offset from `now`
+/// (`+30m`, `+12h`, `+7d`, `+2w`), a local date (`2025-06-30`, which means
+/// the end of that day) or a local date and time (`2025-06-30 18:00`).
+/// Blank means never. The result is UTC, so later checks do not depend on
+/// the time zone.
+pub fn parse_expiry(input: &str, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, &'static str> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let invalid = || "Expiry must look like +7d, +12h, YYYY-MM-DD or YYYY-MM-DD HH:MM";
+    if let Some(offset) = input.strip_prefix('+') {
+        let split = offset.len().saturating_sub(1);
+        let (amount, unit) = offset.split_at_checked(split).ok_or_else(invalid)?;
+        let amount = amount.parse::<u32>().map_err(|_| invalid())?.into();
+        let delta = match unit {
+            "m" => TimeDelta::try_minutes(amount),
+            "h" => TimeDelta::try_hours(amount),
+            "d" => TimeDelta::try_days(amount),
+            "w" => TimeDelta::try_weeks(amount),
+            _ => None,
+        };
+        return delta.and_then(|delta| now.checked_add_signed(delta)).map(Some).ok_or_else(invalid);
+    }
+    let local = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .map(|date| date.and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap_or_default()))
+        })
+        .map_err(|_| invalid())?;
+    // A time skipped by a DST change has no local meaning; the earlier of
+    // two repeated ones is taken.
+    local
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|at| Some(at.with_timezone(&Utc)))
+        .ok_or("Expiry falls in a daylight saving gap")
+}
+
+/// A compact time until `then`, such as "in 5m", "in 3h" or "in 2d".
+/// Past times are "now".
+pub fn until(then: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = (then - now).num_seconds();
+    match secs {
+        ..60 => "now".to_string(),
+        60..3600 => format!("in {}m", secs / 60),
+        3600..86400 => format!("in {}h", secs / 3600),
+        _ => format!("in {}d", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(secs_before: i64) -> (DateTime<Utc>, DateTime<Utc>) {
+        let now = Utc.with_ymd_and_hms(2025, 6, 15, 12, 0, 0).unwrap();
+        (now - TimeDelta::seconds(secs_before), now)
+    }
+
+    fn relative_ago(secs: i64) -> String {
+        let (then, now) = at(secs);
+        relative(then, now)
+    }
+
+    #[test]
+    fn relative_covers_every_range() {
+        assert_eq!(relative_ago(0), "just now");
+        assert_eq!(relative_ago(59), "just now");
+        assert_eq!(relative_ago(60), "1m ago");
+        assert_eq!(relative_ago(3599), "59m ago");
+        assert_eq!(relative_ago(3600), "1h ago");
+        assert_eq!(relative_ago(86399), "23h ago");
+        assert_eq!(relative_ago(86400), "yesterday");
+        assert_eq!(relative_ago(2 * 86400), "2d ago");
+        assert_eq!(relative_ago(7 * 86400), "1w ago");
+        assert_eq!(relative_ago(364 * 86400), "52w ago");
+        assert_eq!(relative_ago(365 * 86400), "1y ago");
+        assert_eq!(relative_ago(3 * 365 * 86400 + 10), "3y ago");
+    }
+
+    #[test]
+    fn small_clock_skew_is_just_now() {
+        assert_eq!(relative_ago(-30), "just now");
+        assert_eq!(relative_ago(-60), "just now");
+        assert_eq!(relative_ago(-61), "in the future");
+        assert_eq!(relative_ago(-86400 * 400), "in the future");
+    }
+
+    #[test]
+    fn absolute_uses_the_pattern_or_falls_back() {
+        // Midsummer noon UTC is the same date and year in every time zone.
+        let (then, _) = at(0);
+        assert_eq!(absolute(then, "%Y"), "2025");
+        assert_eq!(absolute(then, "%Y-%m-%d"), "2025-06-15");
+        assert_eq!(absolute(then, "%Q broken"), absolute(then, DEFAULT_TIMESTAMP_FORMAT));
+        assert!(absolute(then, DEFAULT_TIMESTAMP_FORMAT).starts_with("2025-06-15 "));
+    }
+
+    #[test]
+    fn durations_use_at_most_two_units() {
+        let secs = Duration::from_secs;
+        assert_eq!(duration(secs(0)), "0s");
+        assert_eq!(duration(secs(45)), "45s");
+        assert_eq!(duration(secs(192)), "3m 12s");
+        assert_eq!(duration(secs(4320)), "1h 12m");
+        assert_eq!(duration(secs(2 * 86400 + 3 * 3600 + 59)), "2d 3h");
+        assert_eq!(duration(Duration::from_millis(999)), "0s");
+    }
+
+    #[test]
+    fn expiry_offsets_and_dates() {
+        let (now, _) = at(0);
+        assert_eq!(parse_expiry("", now), Ok(None));
+        assert_eq!(parse_expiry(" +30m ", now), Ok(Some(now + TimeDelta::minutes(30))));
+        assert_eq!(parse_expiry("+12h", now), Ok(Some(now + TimeDelta::hours(12))));
+        assert_eq!(parse_expiry("+7d", now), Ok(Some(now + TimeDelta::days(7))));
+        assert_eq!(parse_expiry("+2w", now), Ok(Some(now + TimeDelta::weeks(2))));
+        for bad in ["+", "+d", "+7y", "+-1d", "tomorrow", "2025-13-01", "2025-06-30 25:00"] {
+            assert!(parse_expiry(bad, now).is_err(), "{}", bad);
+        }
+
+        let end_of_day = parse_expiry("2025-06-30", now).unwrap().unwrap().with_timezone(&Local);
+        assert_eq!(end_of_day.format("%Y-%m-%d %H:%M:%S").to_string(), "2025-06-30 23:59:59");
+        let evening = parse_expiry("2025-06-30 18:00", now).unwrap().unwrap().with_timezone(&Local);
+        assert_eq!(evening.format("%Y-%m-%d %H:%M").to_string(), "2025-06-30 18:00");
+    }
+
+    #[test]
+    fn until_counts_down() {
+        let (now, _) = at(0);
+        let later = |secs: i64| until(now + TimeDelta::seconds(secs), now);
+        assert_eq!(later(-10), "now");
+        assert_eq!(later(59), "now");
+        assert_eq!(later(60), "in 1m");
+        assert_eq!(later(3 * 3600), "in 3h");
+        assert_eq!(later(2 * 86400), "in 2d");
+    }
+
+    #[test]
+    fn styles_toggle() {
+        assert_eq!(TimestampStyle::default(), TimestampStyle::Relative);
+        assert_eq!(TimestampStyle::Relative.next().next(), TimestampStyle::Relative);
+        assert_eq!(TimestampStyle::Absolute.label(), "absolute");
+    }
+}
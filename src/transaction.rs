@@ -0,0 +1,63 @@
+use crate::SshConnection;
+
+/// One change made by a transaction, with what undoing it needs.
+/// Connections are found again by name, since the list may have been
+/// reordered in the meantime.
+#[derive(Debug, Clone)]
+pub enum Change {
+    /// Added; undone by removing the connection with this name.
+    Added(String),
+    /// Changed from `before`; undone by putting `before` back in place of
+    /// the connection now called `name`.
+    Updated { name: String, before: SshConnection },
+    /// Deleted from the index; undone by inserting it there again.
+    Removed(usize, SshConnection),
+}
+
+/// A batch of changes to the connections, made on a working copy. See
+/// `App::transaction`, which validates and commits it as a whole.
+#[derive(Debug)]
+pub struct Transaction {
+    connections: Vec<SshConnection>,
+    changes: Vec<Change>,
+}
+
+impl Transaction {
+    pub(crate) fn new(connections: Vec<SshConnection>) -> Self {
+        Self { connections, changes: Vec::new() }
+    }
+
+    /// The working copy, with the changes made so far.
+    pub fn connections(&self) -> &[SshConnection] {
+        &self.connections
+    }
+
+    pub fn push(&mut self, conn: SshConnection) {
+        self.changes.push(Change::Added(conn.name.clone()));
+        self.connections.push(conn);
+    }
+
+    /// Applies `edit` to the connection at `idx`; out of range does nothing.
+    pub fn update(&mut self, idx: usize, edit: impl FnOnce(&mut SshConnection)) {
+        let Some(conn) = self.connections.get_mut(idx) else {
+            return;
+        };
+        let before = conn.clone();
+        edit(conn);
+        self.changes.push(Change::Updated { name: conn.name.clone(), before });
+    }
+
+    /// Connections added or changed, as they are now.
+    pub(crate) fn touched(&self) -> impl Iterator<Item = &SshConnection> {
+        self.changes.iter().filter_map(|change| match change {
+            Change::Added(name) | Change::Updated { name, .. } => {
+                self.connections.iter().find(|conn| &conn.name == name)
+            }
+            Change::Removed(..) => None,
+        })
+    }
+
+    pub(crate) fn into_parts(self) -> (Vec<SshConnection>, Vec<Change>) {
+        (self.connections, self.changes)
+    }
+}
@@ -0,0 +1,411 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use ssh2::{ErrorCode, FileStat, OpenFlags, OpenType, Session, Sftp};
+use crate::AppError;
+
+/// Bytes read from one side and written to the other at a time.
+const CHUNK_BYTES: usize = 64 * 1024;
+/// How often the transfer thread reports progress.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+/// Permissions for an uploaded file when the local ones are unknown.
+const DEFAULT_MODE: i32 = 0o644;
+const LIBSSH2_FX_NO_SUCH_FILE: i32 = 2;
+const LIBSSH2_FX_PERMISSION_DENIED: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Upload,
+    Download,
+}
+
+/// What the transfer thread reports.
+#[derive(Debug)]
+enum TransferEvent {
+    /// The size of the file, once the remote one has been looked at.
+    Size(u64),
+    /// Bytes copied so far.
+    Sent(u64),
+    /// The transfer is over: `Ok` with the bytes copied, or why not.
+    Finished(Result<u64, String>),
+}
+
+/// A file streamed over SFTP on a background thread, either way. Progress
+/// is picked up with `poll` from the event loop; `cancel` or dropping the
+/// transfer stops the thread, which then removes the partial file.
+#[derive(Debug)]
+pub struct Transfer {
+    pub direction: Direction,
+    pub connection: String,
+    pub local: PathBuf,
+    pub remote: String,
+    /// Size of the file, when known.
+    pub total: Option<u64>,
+    pub sent: u64,
+    /// How the transfer ended, once it has.
+    pub finished: Option<Result<u64, String>>,
+    pub started: Instant,
+    receiver: Receiver<TransferEvent>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Transfer {
+    /// Starts uploading `local` to `remote`, keeping its permission bits.
+    /// `connect` opens and authenticates the session; it runs on the
+    /// transfer thread so the UI does not wait for it.
+    pub fn upload<F>(connection: String, local: PathBuf, remote: String, connect: F) -> Result<Self, AppError>
+    where
+        F: FnOnce() -> Result<Session, AppError> + Send + 'static,
+    {
+        let file = File::open(&local)
+            .map_err(|e| AppError::ConnectionFailed(format!("Could not open {}: {}", local.display(), e)))?;
+        let total = file.metadata().map(|meta| meta.len()).ok();
+        let mode = file_mode(&file);
+        let target = remote.clone();
+        Ok(Self::spawn(Direction::Upload, connection, local, remote, total, move |sender, stop| {
+            let sess = connect().map_err(|e| e.to_string())?;
+            send(&sess, file, &target, mode, sender, stop)
+        }))
+    }
+
+    /// Starts downloading `remote` to `local`, which is only replaced once
+    /// the whole file has arrived.
+    pub fn download<F>(connection: String, remote: String, local: PathBuf, connect: F) -> Self
+    where
+        F: FnOnce() -> Result<Session, AppError> + Send + 'static,
+    {
+        let (source, target) = (remote.clone(), local.clone());
+        Self::spawn(Direction::Download, connection, local, remote, None, move |sender, stop| {
+            let sess = connect().map_err(|e| e.to_string())?;
+            receive(&sess, &source, &target, sender, stop)
+        })
+    }
+
+    fn spawn<F>(direction: Direction, connection: String, local: PathBuf, remote: String, total: Option<u64>, work: F) -> Self
+    where
+        F: FnOnce(&Sender<TransferEvent>, &AtomicBool) -> Result<u64, String> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            let result = work(&sender, &thread_stop);
+            let _ = sender.send(TransferEvent::Finished(result));
+        });
+        Self {
+            direction,
+            connection,
+            local,
+            remote,
+            total,
+            sent: 0,
+            finished: None,
+            started: Instant::now(),
+            receiver,
+            stop,
+        }
+    }
+
+    /// Takes in the progress reported since the last call.
+    pub fn poll(&mut self) {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(TransferEvent::Size(size)) => self.total = Some(size),
+                Ok(TransferEvent::Sent(sent)) => self.sent = sent,
+                Ok(TransferEvent::Finished(result)) => {
+                    if let Ok(sent) = result {
+                        self.sent = sent;
+                    }
+                    self.finished.get_or_insert(result);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.finished.get_or_insert_with(|| Err("The transfer stopped".to_string()));
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Asks the transfer thread to stop; `poll` reports when it has.
+    pub fn cancel(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Progress in whole percent, once the size is known; an empty file
+    /// is done at once.
+    pub fn percent(&self) -> Option<u64> {
+        match self.total? {
+            0 => Some(100),
+            total => Some((self.sent.min(total) * 100) / total),
+        }
+    }
+}
+
+impl Drop for Transfer {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Copies from `reader` to `writer` in chunks, reporting progress, until
+/// the end or `stop`. Errors name the byte offset reached.
+fn copy_chunks(mut reader: impl Read, mut writer: impl Write, sender: &Sender<TransferEvent>, stop: &AtomicBool) -> Result<u64, String> {
+    let mut buf = vec![0u8; CHUNK_BYTES];
+    let mut sent = 0u64;
+    let mut last_report = Instant::now();
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return Err(format!("Cancelled after {} bytes", sent));
+        }
+        let read = match reader.read(&mut buf) {
+            Ok(0) => return Ok(sent),
+            Ok(read) => read,
+            Err(e) => return Err(format!("Reading failed after {} bytes: {}", sent, e)),
+        };
+        writer
+            .write_all(&buf[..read])
+            .map_err(|e| format!("Writing failed after {} bytes: {}", sent, e))?;
+        sent += read as u64;
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            let _ = sender.send(TransferEvent::Sent(sent));
+            last_report = Instant::now();
+        }
+    }
+}
+
+/// Uploads `file` into `<remote>.part` and renames it over `remote` once
+/// complete, so a cancelled or failed upload never touches an existing file.
+fn send(sess: &Session, file: File, remote: &str, mode: i32, sender: &Sender<TransferEvent>, stop: &AtomicBool) -> Result<u64, String> {
+    let sftp = start_sftp(sess)?;
+    let path = Path::new(remote);
+    let partial = part_path(path);
+    let mut target = sftp
+        .open_mode(&partial, OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE, mode, OpenType::File)
+        .map_err(|e| sftp_error(&partial.to_string_lossy(), e))?;
+    let outcome = copy_chunks(file, &mut target, sender, stop).and_then(|sent| {
+        target
+            .close()
+            .map(|_| sent)
+            .map_err(|e| format!("Finishing {} failed after {} bytes: {}", remote, sent, e.message()))
+    });
+    drop(target);
+    let sent = match outcome {
+        Ok(sent) => sent,
+        Err(reason) => {
+            let _ = sftp.unlink(&partial);
+            return Err(reason);
+        }
+    };
+
+    // A leftover .part from an earlier upload keeps its old permissions
+    // through the open above.
+    let stat = FileStat { size: None, uid: None, gid: None, perm: Some(mode as u32), atime: None, mtime: None };
+    let _ = sftp.setstat(&partial, stat);
+    if let Err(e) = replace_remote(&sftp, &partial, path) {
+        let _ = sftp.unlink(&partial);
+        return Err(format!("Could not move the upload to {}: {}", remote, e.message()));
+    }
+    Ok(sent)
+}
+
+/// Renames `from` over `to`. SFTP version 3 servers refuse to rename onto
+/// an existing file, so on failure the target is removed and the rename
+/// tried once more.
+fn replace_remote(sftp: &Sftp, from: &Path, to: &Path) -> Result<(), ssh2::Error> {
+    match sftp.rename(from, to, None) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            if sftp.stat(to).is_err() {
+                return Err(e);
+            }
+            sftp.unlink(to)?;
+            sftp.rename(from, to, None)
+        }
+    }
+}
+
+/// Where a file is written while it is still arriving.
+fn part_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    PathBuf::from(partial)
+}
+
+/// Downloads `remote` to `local` on the calling thread, for callers
+/// without an event loop to follow progress from. Like a background
+/// download, `local` is only replaced once the whole file has arrived.
+pub fn download_now(sess: &Session, remote: &str, local: &Path) -> Result<u64, String> {
+    let (sender, _receiver) = mpsc::channel();
+    receive(sess, remote, local, &sender, &AtomicBool::new(false))
+}
+
+/// Downloads `remote` over SFTP into `local`; see `write_replacing`.
+fn receive(sess: &Session, remote: &str, local: &Path, sender: &Sender<TransferEvent>, stop: &AtomicBool) -> Result<u64, String> {
+    let sftp = start_sftp(sess)?;
+    let path = Path::new(remote);
+    let stat = sftp.stat(path).map_err(|e| sftp_error(remote, e))?;
+    if stat.is_dir() {
+        return Err(format!("{} is a directory", remote));
+    }
+    if let Some(size) = stat.size {
+        let _ = sender.send(TransferEvent::Size(size));
+    }
+    let source = sftp.open(path).map_err(|e| sftp_error(remote, e))?;
+    write_replacing(source, local, sender, stop)
+}
+
+/// Writes `source` into `<local>.part` and renames it over `local` once
+/// complete, so a failure never leaves half a file where `local` was.
+fn write_replacing(source: impl Read, local: &Path, sender: &Sender<TransferEvent>, stop: &AtomicBool) -> Result<u64, String> {
+    let partial = part_path(local);
+    let mut target = File::create(&partial).map_err(|e| format!("Could not create {}: {}", partial.display(), e))?;
+    let outcome = copy_chunks(source, &mut target, sender, stop)
+        .and_then(|sent| target.sync_all().map(|_| sent).map_err(|e| format!("Writing failed after {} bytes: {}", sent, e)));
+    drop(target);
+    match outcome {
+        Ok(sent) => {
+            fs::rename(&partial, local).map_err(|e| format!("Could not move the download to {}: {}", local.display(), e))?;
+            Ok(sent)
+        }
+        Err(reason) => {
+            let _ = fs::remove_file(&partial);
+            Err(reason)
+        }
+    }
+}
+
+fn start_sftp(sess: &Session) -> Result<Sftp, String> {
+    sess.sftp().map_err(|e| format!("Failed to start SFTP: {}", e.message()))
+}
+
+/// A readable reason for an SFTP error about `remote`.
+fn sftp_error(remote: &str, e: ssh2::Error) -> String {
+    match e.code() {
+        ErrorCode::SFTP(LIBSSH2_FX_NO_SUCH_FILE) => format!("No such file on the remote host: {}", remote),
+        ErrorCode::SFTP(LIBSSH2_FX_PERMISSION_DENIED) => format!("Permission denied on the remote host: {}", remote),
+        _ => format!("{}: {}", remote, e.message()),
+    }
+}
+
+#[cfg(unix)]
+fn file_mode(file: &File) -> i32 {
+    use std::os::unix::fs::PermissionsExt;
+    file.metadata()
+        .map(|meta| (meta.permissions().mode() & 0o777) as i32)
+        .unwrap_or(DEFAULT_MODE)
+}
+
+#[cfg(not(unix))]
+fn file_mode(_file: &File) -> i32 {
+    DEFAULT_MODE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    /// A fresh, empty directory under the system temp dir.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("peroxide-transfer-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Yields `good` bytes, then fails.
+    struct FailingReader {
+        good: usize,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.good == 0 {
+                return Err(io::Error::other("link dropped"));
+            }
+            let read = self.good.min(buf.len());
+            buf[..read].fill(b'x');
+            self.good -= read;
+            Ok(read)
+        }
+    }
+
+    #[test]
+    fn part_path_appends_suffix() {
+        assert_eq!(part_path(Path::new("/tmp/log.txt")), PathBuf::from("/tmp/log.txt.part"));
+        assert_eq!(part_path(Path::new("notes")), PathBuf::from("notes.part"));
+    }
+
+    #[test]
+    fn completed_write_replaces_target_and_removes_part() {
+        let dir = scratch_dir("complete");
+        let target = dir.join("file.txt");
+        fs::write(&target, b"old contents").unwrap();
+        let (sender, _receiver) = mpsc::channel();
+
+        let sent = write_replacing(&b"new"[..], &target, &sender, &AtomicBool::new(false)).unwrap();
+
+        assert_eq!(sent, 3);
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+        assert!(!part_path(&target).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_write_keeps_existing_target() {
+        let dir = scratch_dir("failed");
+        let target = dir.join("file.txt");
+        fs::write(&target, b"old contents").unwrap();
+        let (sender, _receiver) = mpsc::channel();
+
+        let err = write_replacing(FailingReader { good: CHUNK_BYTES + 10 }, &target, &sender, &AtomicBool::new(false))
+            .unwrap_err();
+
+        assert!(err.contains(&format!("after {} bytes", CHUNK_BYTES + 10)), "{}", err);
+        assert_eq!(fs::read(&target).unwrap(), b"old contents");
+        assert!(!part_path(&target).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_write_leaves_nothing_behind() {
+        let dir = scratch_dir("cancelled");
+        let target = dir.join("file.txt");
+        let (sender, _receiver) = mpsc::channel();
+
+        let err = write_replacing(&b"data"[..], &target, &sender, &AtomicBool::new(true)).unwrap_err();
+
+        assert_eq!(err, "Cancelled after 0 bytes");
+        assert!(!target.exists());
+        assert!(!part_path(&target).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn empty_transfer_is_complete() {
+        let (_sender, receiver) = mpsc::channel();
+        let transfer = Transfer {
+            direction: Direction::Download,
+            connection: "web".to_string(),
+            local: PathBuf::from("file"),
+            remote: "file".to_string(),
+            total: Some(0),
+            sent: 0,
+            finished: None,
+            started: Instant::now(),
+            receiver,
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+        assert_eq!(transfer.percent(), Some(100));
+    }
+}
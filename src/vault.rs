@@ -0,0 +1,464 @@
+use std::fmt;
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::secret::Secret;
+
+/// Version 1 files seal the whole connection list at once.
+const SEALED_VERSION: u32 = 1;
+/// Version 2 files keep the connections readable and seal each secret on
+/// its own.
+const SECRETS_VERSION: u32 = 2;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+/// Sealed into every version 2 header, so a wrong master password is
+/// caught even when no secret is saved.
+const CHECK_PLAIN: &[u8] = b"peroxide";
+/// Starts every `.perox` export, followed by its version byte.
+const EXPORT_MAGIC: &[u8] = b"PEROX";
+const EXPORT_VERSION: u8 = 1;
+
+/// A version 1 encrypted connections file. Plain files are a JSON array,
+/// so the object form tells the two apart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sealed {
+    /// Format version.
+    pub encrypted: u32,
+    /// Always `argon2id`, with the crate's default parameters.
+    pub kdf: String,
+    /// Hex encoded.
+    pub salt: String,
+    /// Hex encoded XChaCha20-Poly1305 nonce.
+    pub nonce: String,
+    /// Hex encoded ciphertext and tag.
+    pub ciphertext: String,
+}
+
+/// The header of a version 2 encrypted connections file, next to the
+/// connections themselves.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Header {
+    /// Format version.
+    pub encrypted: u32,
+    /// Always `argon2id`, with the crate's default parameters.
+    pub kdf: String,
+    /// Hex encoded.
+    pub salt: String,
+    /// `CHECK_PLAIN` sealed like a secret.
+    pub check: String,
+}
+
+/// A version 2 file as written to disk.
+#[derive(Serialize, Deserialize)]
+struct SecretsFile<T> {
+    #[serde(flatten)]
+    header: Header,
+    connections: T,
+}
+
+/// What an encrypted connections file holds.
+pub enum Envelope<T> {
+    /// Version 1: nothing can be read without the master password.
+    Sealed(Sealed),
+    /// Version 2: the connections, whose secret fields are sealed one by
+    /// one with `Vault::seal_secret`.
+    Secrets(Header, T),
+}
+
+/// `None` for a plain JSON file, otherwise the parsed envelope.
+pub fn detect<T: DeserializeOwned>(content: &str) -> Option<Result<Envelope<T>>> {
+    if !content.trim_start().starts_with('{') {
+        return None;
+    }
+    #[derive(Deserialize)]
+    struct Version {
+        encrypted: u32,
+    }
+    let version = match serde_json::from_str::<Version>(content) {
+        Ok(version) => version.encrypted,
+        Err(e) => return Some(Err(anyhow::Error::new(e).context("Not a peroxide encrypted file"))),
+    };
+    Some(match version {
+        SEALED_VERSION => serde_json::from_str(content).map(Envelope::Sealed).context("Not a peroxide encrypted file"),
+        SECRETS_VERSION => serde_json::from_str::<SecretsFile<T>>(content)
+            .map(|file| Envelope::Secrets(file.header, file.connections))
+            .context("Encrypted connections file is damaged"),
+        version => Err(anyhow!("Unsupported encrypted file version {}", version)),
+    })
+}
+
+/// Encryption of the connections file under a master password. The salt
+/// and check value are always known; the password and the key derived
+/// from it only while unlocked, so only the first unlock pays for argon2
+/// and `lock` drops them again.
+pub struct Vault {
+    salt: [u8; SALT_LEN],
+    check: String,
+    unlocked: Option<(Secret, Key)>,
+}
+
+impl fmt::Debug for Vault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Vault { .. }")
+    }
+}
+
+impl Vault {
+    /// An unlocked vault for a new master password, with a fresh salt.
+    pub fn new(password: &str) -> Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(password, salt)
+    }
+
+    fn derive(password: &str, salt: [u8; SALT_LEN]) -> Result<Self> {
+        let key = derive_key(password, &salt)?;
+        let check = seal_with(&key, CHECK_PLAIN)?;
+        Ok(Self {
+            salt,
+            check,
+            unlocked: Some((Secret::from(password), key)),
+        })
+    }
+
+    /// A locked vault for a version 2 file.
+    pub fn from_header(header: &Header) -> Result<Self> {
+        let salt = from_hex(&header.salt)
+            .and_then(|salt| salt.try_into().ok())
+            .context("Encrypted file has a damaged salt")?;
+        Ok(Self {
+            salt,
+            check: header.check.clone(),
+            unlocked: None,
+        })
+    }
+
+    /// Decrypts a version 1 file with `password`, returning an unlocked
+    /// vault to save with from now on and the plaintext.
+    pub fn unseal(sealed: &Sealed, password: &str) -> Result<(Self, Vec<u8>)> {
+        let salt: [u8; SALT_LEN] = from_hex(&sealed.salt)
+            .and_then(|salt| salt.try_into().ok())
+            .context("Encrypted file has a damaged salt")?;
+        let vault = Self::derive(password, salt)?;
+        let plain = vault.open(sealed)?;
+        Ok((vault, plain))
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.unlocked.is_some()
+    }
+
+    /// Keeps the key for `password` once it proves to be the master
+    /// password.
+    pub fn unlock(&mut self, password: &str) -> Result<()> {
+        let key = self.key_if_correct(password)?;
+        self.unlocked = Some((Secret::from(password), key));
+        Ok(())
+    }
+
+    /// Forgets the password and key until the next `unlock`.
+    pub fn lock(&mut self) {
+        self.unlocked = None;
+    }
+
+    /// Fails unless `password` is the master password this vault was made
+    /// with. Works locked or not, and leaves it as it was.
+    pub fn check(&self, password: &str) -> Result<()> {
+        self.key_if_correct(password).map(drop)
+    }
+
+    fn key_if_correct(&self, password: &str) -> Result<Key> {
+        let key = derive_key(password, &self.salt)?;
+        open_with(&key, &self.check).map_err(|_| anyhow!("Wrong master password"))?;
+        Ok(key)
+    }
+
+    fn key(&self) -> Result<&Key> {
+        self.unlocked.as_ref().map(|(_, key)| key).context("The master password is needed first")
+    }
+
+    /// The key for another file written under the same master password,
+    /// such as an older backup with its own salt.
+    fn key_for_salt(&self, salt: &str) -> Result<Key> {
+        let salt = from_hex(salt).context("Encrypted file has a damaged salt")?;
+        match &self.unlocked {
+            Some((_, key)) if salt == self.salt => Ok(*key),
+            Some((password, _)) => derive_key(password.expose(), &salt),
+            None => bail!("The master password is needed first"),
+        }
+    }
+
+    /// Decrypts a version 1 file, which may have been written with another
+    /// salt (an older backup under the same password). Needs the vault
+    /// unlocked.
+    pub fn open(&self, sealed: &Sealed) -> Result<Vec<u8>> {
+        let key = self.key_for_salt(&sealed.salt)?;
+        let nonce = from_hex(&sealed.nonce)
+            .filter(|nonce| nonce.len() == NONCE_LEN)
+            .context("Encrypted file has a damaged nonce")?;
+        let ciphertext = from_hex(&sealed.ciphertext).context("Encrypted file is damaged")?;
+        XChaCha20Poly1305::new(&key)
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| anyhow!("Wrong master password (or the file is damaged)"))
+    }
+
+    /// Seals one secret, hex encoded with its own nonce. Needs the vault
+    /// unlocked.
+    pub fn seal_secret(&self, secret: &Secret) -> Result<String> {
+        seal_with(self.key()?, secret.expose().as_bytes())
+    }
+
+    /// Opens a secret sealed by `seal_secret`. Needs the vault unlocked.
+    pub fn open_secret(&self, sealed: &str) -> Result<Secret> {
+        open_secret_with(self.key()?, sealed)
+    }
+
+    /// Opens a secret sealed in the file with `header`, which may have its
+    /// own salt. Needs the vault unlocked.
+    pub fn open_foreign_secret(&self, header: &Header, sealed: &str) -> Result<Secret> {
+        open_secret_with(&self.key_for_salt(&header.salt)?, sealed)
+    }
+
+    /// The JSON text of a version 2 file holding `connections`, whose
+    /// secrets the caller has already sealed.
+    pub fn wrap<T: Serialize>(&self, connections: &T) -> Result<String> {
+        let file = SecretsFile {
+            header: Header {
+                encrypted: SECRETS_VERSION,
+                kdf: "argon2id".to_string(),
+                salt: to_hex(&self.salt),
+                check: self.check.clone(),
+            },
+            connections,
+        };
+        Ok(serde_json::to_string_pretty(&file)?)
+    }
+}
+
+/// Encrypts `plain` under `key` as hex encoded nonce, ciphertext and tag.
+fn seal_with(key: &Key, plain: &[u8]) -> Result<String> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = XChaCha20Poly1305::new(key)
+        .encrypt(&nonce, plain)
+        .map_err(|_| anyhow!("Encryption failed"))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(to_hex(&sealed))
+}
+
+fn open_secret_with(key: &Key, sealed: &str) -> Result<Secret> {
+    let plain = open_with(key, sealed)?;
+    String::from_utf8(plain).map(Secret::new).context("A sealed secret is damaged")
+}
+
+fn open_with(key: &Key, sealed: &str) -> Result<Vec<u8>> {
+    let sealed = from_hex(sealed)
+        .filter(|sealed| sealed.len() >= NONCE_LEN)
+        .context("A sealed secret is damaged")?;
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(key)
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Wrong master password (or a sealed secret is damaged)"))
+}
+
+/// Whether `data` starts like a `.perox` export.
+pub fn is_export(data: &[u8]) -> bool {
+    data.starts_with(EXPORT_MAGIC)
+}
+
+/// Encrypts `plain` under `passphrase` as a `.perox` export: the magic and
+/// version byte, a fresh salt and nonce, then the ciphertext and tag. The
+/// header is authenticated along with the ciphertext.
+pub fn seal_export(plain: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt)?;
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let mut out = EXPORT_MAGIC.to_vec();
+    out.push(EXPORT_VERSION);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    let ciphertext = XChaCha20Poly1305::new(&key)
+        .encrypt(&nonce, Payload { msg: plain, aad: &out })
+        .map_err(|_| anyhow!("Encryption failed"))?;
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypts a `.perox` export made by `seal_export`.
+pub fn open_export(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let rest = data.strip_prefix(EXPORT_MAGIC).context("Not a peroxide export file")?;
+    let (&version, rest) = rest.split_first().context("Export file is damaged")?;
+    if version != EXPORT_VERSION {
+        bail!("Unsupported export file version {}", version);
+    }
+    if rest.len() < SALT_LEN + NONCE_LEN {
+        bail!("Export file is damaged");
+    }
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let header = &data[..data.len() - ciphertext.len()];
+    XChaCha20Poly1305::new(&derive_key(passphrase, salt)?)
+        .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad: header })
+        .map_err(|_| anyhow!("Decryption failed (wrong passphrase, or the file is damaged)"))
+}
+
+fn derive_key(password: &str, salt: &[u8]) -> Result<Key> {
+    if password.is_empty() {
+        bail!("The master password cannot be empty");
+    }
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_round_trips_and_starts_with_its_version() {
+        let plain = br#"[{"name":"web"}]"#;
+        let sealed = seal_export(plain, "correct horse").unwrap();
+        assert!(is_export(&sealed));
+        assert_eq!(&sealed[..5], b"PEROX");
+        assert_eq!(sealed[5], EXPORT_VERSION);
+        assert_eq!(sealed.len(), 6 + SALT_LEN + NONCE_LEN + plain.len() + 16);
+        assert_eq!(open_export(&sealed, "correct horse").unwrap(), plain);
+        // A fresh salt and nonce every time.
+        assert_ne!(seal_export(plain, "correct horse").unwrap(), sealed);
+    }
+
+    #[test]
+    fn export_with_wrong_passphrase_or_damage_fails_cleanly() {
+        let sealed = seal_export(b"[]", "correct horse").unwrap();
+        let err = open_export(&sealed, "battery staple").unwrap_err().to_string();
+        assert!(err.starts_with("Decryption failed"), "{}", err);
+
+        // The header is authenticated too.
+        let mut tampered = sealed.clone();
+        tampered[6] ^= 1;
+        assert!(open_export(&tampered, "correct horse").unwrap_err().to_string().starts_with("Decryption failed"));
+
+        let mut newer = sealed.clone();
+        newer[5] = EXPORT_VERSION + 1;
+        assert_eq!(open_export(&newer, "correct horse").unwrap_err().to_string(), "Unsupported export file version 2");
+        assert_eq!(open_export(&sealed[..20], "correct horse").unwrap_err().to_string(), "Export file is damaged");
+        assert!(!is_export(b"[]"));
+        assert_eq!(open_export(b"[]", "correct horse").unwrap_err().to_string(), "Not a peroxide export file");
+    }
+
+    /// A version 1 file, which peroxide no longer writes but still reads.
+    fn seal_v1(password: &str, plain: &[u8]) -> String {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let key = derive_key(password, &salt).unwrap();
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(&key).encrypt(&nonce, plain).unwrap();
+        serde_json::to_string(&Sealed {
+            encrypted: SEALED_VERSION,
+            kdf: "argon2id".to_string(),
+            salt: to_hex(&salt),
+            nonce: to_hex(&nonce),
+            ciphertext: to_hex(&ciphertext),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn version_1_files_unseal_with_the_master_password() {
+        let Some(Ok(Envelope::<()>::Sealed(sealed))) = detect(&seal_v1("master", b"secret list")) else {
+            panic!("not detected as version 1");
+        };
+        let (vault, plain) = Vault::unseal(&sealed, "master").unwrap();
+        assert_eq!(plain, b"secret list");
+        assert!(vault.is_unlocked());
+        assert!(vault.check("master").is_ok());
+        // Another file under the same password, with its own salt.
+        let Some(Ok(Envelope::<()>::Sealed(older))) = detect(&seal_v1("master", b"older")) else {
+            panic!("not detected as version 1");
+        };
+        assert_eq!(vault.open(&older).unwrap(), b"older");
+
+        let err = Vault::unseal(&sealed, "wrong").unwrap_err().to_string();
+        assert!(err.starts_with("Wrong master password"), "{}", err);
+        assert_eq!(vault.check("wrong").unwrap_err().to_string(), "Wrong master password");
+        assert!(Vault::new("").is_err());
+    }
+
+    #[test]
+    fn secrets_are_sealed_one_by_one_and_need_the_vault_unlocked() {
+        let vault = Vault::new("master").unwrap();
+        let sealed = vault.seal_secret(&Secret::from("hunter2")).unwrap();
+        assert_ne!(vault.seal_secret(&Secret::from("hunter2")).unwrap(), sealed, "every secret gets its own nonce");
+        assert_eq!(vault.open_secret(&sealed).unwrap().expose(), "hunter2");
+
+        let text = vault.wrap(&["web"]).unwrap();
+        let Some(Ok(Envelope::Secrets(header, names))) = detect::<Vec<String>>(&text) else {
+            panic!("not detected as version 2");
+        };
+        assert_eq!(header.encrypted, SECRETS_VERSION);
+        assert_eq!(names, ["web"], "the connections stay readable");
+        assert!(!text.contains("hunter2"));
+
+        let mut reopened = Vault::from_header(&header).unwrap();
+        assert!(!reopened.is_unlocked());
+        let err = reopened.open_secret(&sealed).unwrap_err().to_string();
+        assert_eq!(err, "The master password is needed first");
+        assert!(reopened.seal_secret(&Secret::from("x")).is_err());
+        assert_eq!(reopened.unlock("wrong").unwrap_err().to_string(), "Wrong master password");
+        assert!(!reopened.is_unlocked());
+        reopened.unlock("master").unwrap();
+        assert_eq!(reopened.open_secret(&sealed).unwrap().expose(), "hunter2");
+        reopened.lock();
+        assert!(reopened.open_secret(&sealed).is_err());
+        assert!(reopened.check("master").is_ok(), "checking works while locked");
+    }
+
+    #[test]
+    fn secrets_from_another_file_open_with_its_salt() {
+        let theirs = Vault::new("master").unwrap();
+        let sealed = theirs.seal_secret(&Secret::from("hunter2")).unwrap();
+        let Some(Ok(Envelope::Secrets(header, ()))) = detect(&theirs.wrap(&()).unwrap()) else {
+            panic!("not detected as version 2");
+        };
+        let ours = Vault::new("master").unwrap();
+        assert!(ours.open_secret(&sealed).is_err(), "a different salt means a different key");
+        assert_eq!(ours.open_foreign_secret(&header, &sealed).unwrap().expose(), "hunter2");
+        assert!(Vault::new("other").unwrap().open_foreign_secret(&header, &sealed).is_err());
+    }
+
+    #[test]
+    fn plain_files_and_unknown_versions_are_told_apart() {
+        assert!(detect::<()>("[]").is_none());
+        let future = r#"{"encrypted":3,"kdf":"argon2id","salt":"","nonce":"","ciphertext":""}"#;
+        let Some(Err(err)) = detect::<()>(future) else {
+            panic!("version 3 accepted");
+        };
+        assert_eq!(err.to_string(), "Unsupported encrypted file version 3");
+        assert_eq!(from_hex("0aff"), Some(vec![0x0a, 0xff]));
+        assert_eq!(from_hex("0af"), None);
+        assert_eq!(to_hex(&[0x0a, 0xff]), "0aff");
+    }
+}